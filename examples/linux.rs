@@ -5,5 +5,5 @@ fn main() {
     let dev = I2cdev::new("/dev/i2c-1").unwrap();
     let mut sensor = Max44009::new(dev, SlaveAddr::default());
     let lux = sensor.read_lux().unwrap();
-    println!("lux: {}", lux);
+    println!("{}", lux);
 }
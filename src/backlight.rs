@@ -0,0 +1,261 @@
+//! Automatic display backlight control
+//!
+//! Maps (filtered) lux readings to a display brightness following a
+//! logarithmic curve, since the perceived brightness of the ambient light is
+//! roughly proportional to the logarithm of the lux intensity. Brightness
+//! transitions are rate-limited and the result is output as a PWM duty
+//! value.
+
+use crate::{filter::LuxFilter, Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Brightness curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrightnessCurve {
+    /// At or below this lux intensity the minimum brightness is used.
+    pub min_lux: Lux,
+    /// At or above this lux intensity the maximum brightness is used.
+    pub max_lux: Lux,
+    /// Minimum perceived brightness. (0.0 - 1.0)
+    pub min_brightness: f32,
+    /// Maximum perceived brightness. (0.0 - 1.0)
+    pub max_brightness: f32,
+    /// Exponent converting the perceived brightness into the PWM duty.
+    ///
+    /// The eye perceives light output non-linearly so a perceived brightness
+    /// of 0.5 corresponds to a much lower duty cycle. Use 1.0 for a linear
+    /// output.
+    pub gamma: f32,
+}
+
+impl Default for BrightnessCurve {
+    /// From 5% brightness at 1 lux to 100% brightness at 10000 lux with a
+    /// gamma of 2.2.
+    fn default() -> Self {
+        BrightnessCurve {
+            min_lux: Lux::new(1.0),
+            max_lux: Lux::new(10_000.0),
+            min_brightness: 0.05,
+            max_brightness: 1.0,
+            gamma: 2.2,
+        }
+    }
+}
+
+impl BrightnessCurve {
+    /// Perceived brightness for a lux intensity.
+    pub fn brightness(&self, lux: Lux) -> f32 {
+        let min = libm::logf(self.min_lux.value().max(f32::MIN_POSITIVE));
+        let max = libm::logf(self.max_lux.value().max(f32::MIN_POSITIVE));
+        let position = if max > min {
+            (libm::logf(lux.value().max(f32::MIN_POSITIVE)) - min) / (max - min)
+        } else if lux >= self.max_lux {
+            1.0
+        } else {
+            0.0
+        };
+        let position = position.clamp(0.0, 1.0);
+        self.min_brightness + position * (self.max_brightness - self.min_brightness)
+    }
+}
+
+/// Automatic backlight controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BacklightController<F> {
+    curve: BrightnessCurve,
+    filter: F,
+    max_rate_per_s: Option<f32>,
+    max_duty: u16,
+    brightness: Option<f32>,
+}
+
+impl<F: LuxFilter> BacklightController<F> {
+    /// Create a new backlight controller.
+    ///
+    /// `max_rate_per_s` is the maximum change in perceived brightness per
+    /// second, e.g. 0.5 for a full-range transition in 2 seconds. `None`
+    /// disables the rate limit.
+    /// `max_duty` is the PWM duty for 100% brightness.
+    ///
+    /// Panics if `max_rate_per_s` is not a positive finite number.
+    pub fn new(
+        curve: BrightnessCurve,
+        filter: F,
+        max_rate_per_s: Option<f32>,
+        max_duty: u16,
+    ) -> Self {
+        if let Some(max_rate_per_s) = max_rate_per_s {
+            assert!(
+                max_rate_per_s.is_finite() && max_rate_per_s > 0.0,
+                "The maximum rate must be positive and finite."
+            );
+        }
+        BacklightController {
+            curve,
+            filter,
+            max_rate_per_s,
+            max_duty,
+            brightness: None,
+        }
+    }
+
+    /// Current perceived brightness. `None` before the first reading.
+    pub fn brightness(&self) -> Option<f32> {
+        self.brightness
+    }
+
+    /// Process a lux reading and return the PWM duty.
+    ///
+    /// `elapsed_ms` is the time since the previous reading and limits how
+    /// much the brightness may change. The first reading sets the
+    /// brightness directly.
+    pub fn update(&mut self, lux: Lux, elapsed_ms: u32) -> u16 {
+        let filtered = Lux::new(self.filter.update(lux.value()));
+        let target = self.curve.brightness(filtered);
+        let brightness = match (self.brightness, self.max_rate_per_s) {
+            (Some(current), Some(max_rate_per_s)) => {
+                let max_step = max_rate_per_s * elapsed_ms as f32 / 1000.0;
+                current + (target - current).max(-max_step).min(max_step)
+            }
+            _ => target,
+        };
+        self.brightness = Some(brightness);
+        self.duty(brightness)
+    }
+
+    /// Read the lux intensity from the device and return the PWM duty.
+    pub fn poll<I2C, D, E>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        elapsed_ms: u32,
+    ) -> Result<u16, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let lux = sensor.read_lux()?;
+        Ok(self.update(lux, elapsed_ms))
+    }
+
+    /// Forget the current brightness and the filter history.
+    pub fn reset(&mut self) {
+        self.brightness = None;
+        self.filter.reset();
+    }
+
+    fn duty(&self, brightness: f32) -> u16 {
+        let output = libm::powf(brightness.clamp(0.0, 1.0), self.curve.gamma);
+        libm::roundf(output * f32::from(self.max_duty)) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MovingAverage;
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    fn linear_curve() -> BrightnessCurve {
+        BrightnessCurve {
+            min_lux: Lux::new(1.0),
+            max_lux: Lux::new(10_000.0),
+            min_brightness: 0.0,
+            max_brightness: 1.0,
+            gamma: 1.0,
+        }
+    }
+
+    #[test]
+    fn curve_is_logarithmic() {
+        let curve = linear_curve();
+        assert_near(0.0, curve.brightness(Lux::new(0.0)), 0.001);
+        assert_near(0.0, curve.brightness(Lux::new(1.0)), 0.001);
+        assert_near(0.25, curve.brightness(Lux::new(10.0)), 0.001);
+        assert_near(0.5, curve.brightness(Lux::new(100.0)), 0.001);
+        assert_near(1.0, curve.brightness(Lux::new(10_000.0)), 0.001);
+        assert_near(1.0, curve.brightness(Lux::new(100_000.0)), 0.001);
+    }
+
+    #[test]
+    fn curve_respects_brightness_range() {
+        let curve = BrightnessCurve::default();
+        assert_near(0.05, curve.brightness(Lux::new(0.0)), 0.001);
+        assert_near(1.0, curve.brightness(Lux::new(20_000.0)), 0.001);
+    }
+
+    #[test]
+    fn first_reading_sets_brightness() {
+        let mut controller =
+            BacklightController::new(linear_curve(), MovingAverage::<1>::new(), Some(0.5), 1000);
+        assert_eq!(500, controller.update(Lux::new(100.0), 0));
+    }
+
+    #[test]
+    fn limits_rate_of_change() {
+        let mut controller =
+            BacklightController::new(linear_curve(), MovingAverage::<1>::new(), Some(0.5), 1000);
+        controller.update(Lux::new(1.0), 0);
+        assert_eq!(50, controller.update(Lux::new(10_000.0), 100));
+        assert_eq!(300, controller.update(Lux::new(10_000.0), 500));
+        assert_eq!(250, controller.update(Lux::new(1.0), 100));
+    }
+
+    #[test]
+    fn can_disable_rate_limit() {
+        let mut controller =
+            BacklightController::new(linear_curve(), MovingAverage::<1>::new(), None, 1000);
+        controller.update(Lux::new(1.0), 0);
+        assert_eq!(1000, controller.update(Lux::new(10_000.0), 100));
+        assert_eq!(1000, controller.update(Lux::new(10_000.0), 0));
+        assert_eq!(0, controller.update(Lux::new(1.0), 100));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_rate() {
+        BacklightController::new(linear_curve(), MovingAverage::<1>::new(), Some(0.0), 1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_negative_rate() {
+        BacklightController::new(linear_curve(), MovingAverage::<1>::new(), Some(-0.5), 1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_nan_rate() {
+        BacklightController::new(
+            linear_curve(),
+            MovingAverage::<1>::new(),
+            Some(f32::NAN),
+            1000,
+        );
+    }
+
+    #[test]
+    fn applies_gamma() {
+        let curve = BrightnessCurve {
+            gamma: 2.0,
+            ..linear_curve()
+        };
+        let mut controller = BacklightController::new(curve, MovingAverage::<1>::new(), None, 1000);
+        assert_eq!(250, controller.update(Lux::new(100.0), 0));
+    }
+
+    #[test]
+    fn filters_readings() {
+        let mut controller =
+            BacklightController::new(linear_curve(), MovingAverage::<2>::new(), None, 1000);
+        controller.update(Lux::new(10.0), 0);
+        assert_eq!(250, controller.update(Lux::new(10.0), 100));
+        controller.reset();
+        assert_eq!(None, controller.brightness());
+    }
+}
@@ -0,0 +1,416 @@
+//! Threshold-triggered command runner
+//!
+//! The hardware thresholds are programmed to the window between the trigger
+//! levels surrounding the current lux intensity. When the interrupt fires,
+//! the crossed trigger levels run their command and/or write an event line
+//! to their FIFO, and the window is moved around the new lux intensity.
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use linux_embedded_hal::I2cdev;
+use max44009::{Lux, Max44009};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// Highest threshold that can be programmed.
+const MAX_LUX: f32 = 188_000.0;
+
+/// Daemon configuration file contents
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// I²C bus device. Overrides the command-line argument.
+    pub bus: Option<String>,
+    /// Device address. Overrides the command-line argument.
+    pub address: Option<u8>,
+    /// Time between interrupt checks in milliseconds.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Time the lux intensity must stay beyond a level before the trigger
+    /// fires, in milliseconds (multiple of 100, up to 25500).
+    #[serde(default)]
+    pub timer_ms: u32,
+    /// GPIO line connected to the INT pin. If not set, the interrupt status
+    /// register is polled.
+    pub gpio: Option<GpioConfig>,
+    /// Trigger levels.
+    #[serde(rename = "trigger", default)]
+    pub triggers: Vec<Trigger>,
+}
+
+fn default_poll_interval_ms() -> u64 {
+    100
+}
+
+/// GPIO character device line
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GpioConfig {
+    /// GPIO chip device, e.g. `/dev/gpiochip0`.
+    pub chip: PathBuf,
+    /// Line offset.
+    pub line: u32,
+}
+
+/// Action run when the lux intensity crosses a level
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trigger {
+    /// Name reported to the command and in the FIFO events. Defaults to
+    /// the level.
+    #[serde(default)]
+    pub name: String,
+    /// Level in lux.
+    pub lux: f32,
+    /// Crossing direction(s) that fire the trigger.
+    #[serde(default)]
+    pub direction: Direction,
+    /// Shell command to run.
+    ///
+    /// The `MAX44009_TRIGGER`, `MAX44009_LUX` and `MAX44009_DIRECTION`
+    /// environment variables describe the event.
+    pub command: Option<String>,
+    /// FIFO to write a JSON event line to.
+    pub fifo: Option<PathBuf>,
+}
+
+/// Crossing directions that fire a trigger
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From below to above the level.
+    Rising,
+    /// From above to below the level.
+    Falling,
+    /// Both directions.
+    #[default]
+    Both,
+}
+
+/// Direction in which a level was crossed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Crossing {
+    /// From below to above the level.
+    Rising,
+    /// From above to below the level.
+    Falling,
+}
+
+impl Crossing {
+    fn as_str(self) -> &'static str {
+        match self {
+            Crossing::Rising => "rising",
+            Crossing::Falling => "falling",
+        }
+    }
+}
+
+/// Event written to the FIFOs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Event<'a> {
+    trigger: &'a str,
+    lux: f32,
+    direction: Crossing,
+}
+
+impl Trigger {
+    fn label(&self) -> String {
+        if self.name.is_empty() {
+            format!("{} lx", self.lux)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+impl Config {
+    /// Check the configuration for consistency.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.triggers.is_empty() {
+            return Err("at least one trigger must be configured".to_string());
+        }
+        if self.timer_ms % 100 != 0 || self.timer_ms > 25_500 {
+            return Err("timer_ms must be a multiple of 100 up to 25500".to_string());
+        }
+        for trigger in &self.triggers {
+            if !(0.0..=MAX_LUX).contains(&trigger.lux) {
+                return Err(format!(
+                    "trigger '{}': lux must be between 0 and {}",
+                    trigger.label(),
+                    MAX_LUX
+                ));
+            }
+            if trigger.command.is_none() && trigger.fifo.is_none() {
+                return Err(format!(
+                    "trigger '{}': a command or a fifo must be configured",
+                    trigger.label()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hardware threshold window around the lux intensity: the nearest trigger
+/// levels at or below and above it.
+pub fn window(triggers: &[Trigger], lux: f32) -> (f32, f32) {
+    let lower = triggers
+        .iter()
+        .map(|t| t.lux)
+        .filter(|level| *level <= lux)
+        .fold(0.0, f32::max);
+    let upper = triggers
+        .iter()
+        .map(|t| t.lux)
+        .filter(|level| *level > lux)
+        .fold(MAX_LUX, f32::min);
+    (lower, upper)
+}
+
+/// Triggers fired by a change of the lux intensity.
+pub fn crossings(triggers: &[Trigger], previous: f32, current: f32) -> Vec<(&Trigger, Crossing)> {
+    triggers
+        .iter()
+        .filter_map(|trigger| {
+            let crossing = if previous < trigger.lux && trigger.lux <= current {
+                Crossing::Rising
+            } else if current < trigger.lux && trigger.lux <= previous {
+                Crossing::Falling
+            } else {
+                return None;
+            };
+            let fires = matches!(
+                (trigger.direction, crossing),
+                (Direction::Both, _)
+                    | (Direction::Rising, Crossing::Rising)
+                    | (Direction::Falling, Crossing::Falling)
+            );
+            if fires {
+                Some((trigger, crossing))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn fire(trigger: &Trigger, crossing: Crossing, lux: f32) {
+    let name = trigger.label();
+    eprintln!(
+        "Trigger '{}' fired: {} lx ({})",
+        name,
+        lux,
+        crossing.as_str()
+    );
+    if let Some(command) = &trigger.command {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("MAX44009_TRIGGER", &name)
+            .env("MAX44009_LUX", lux.to_string())
+            .env("MAX44009_DIRECTION", crossing.as_str())
+            .spawn();
+        match child {
+            // Reap the child without blocking the daemon.
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => eprintln!("Warning: cannot run '{}': {}", command, e),
+        }
+    }
+    if let Some(fifo) = &trigger.fifo {
+        let event = Event {
+            trigger: &name,
+            lux,
+            direction: crossing,
+        };
+        // Opening a FIFO without a reader fails instead of blocking.
+        let result = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(fifo)
+            .and_then(|mut file| {
+                let line = serde_json::to_string(&event)? + "\n";
+                file.write_all(line.as_bytes())
+            });
+        if let Err(e) = result {
+            eprintln!("Warning: cannot write to {}: {}", fifo.display(), e);
+        }
+    }
+}
+
+/// Source of the interrupt condition
+enum Interrupt {
+    /// Interrupt status register.
+    Status,
+    /// INT pin connected to a GPIO line. The pin is active low.
+    Gpio(LineHandle),
+}
+
+fn program_window(
+    sensor: &mut Max44009<I2cdev>,
+    triggers: &[Trigger],
+    lux: f32,
+) -> Result<(), Box<dyn Error>> {
+    let (lower, upper) = window(triggers, lux);
+    sensor.set_lower_threshold(Lux::new(lower))?;
+    sensor.set_upper_threshold(Lux::new(upper))?;
+    Ok(())
+}
+
+/// Read the lux intensity. Readings above the measurable range are taken as
+/// the maximum.
+fn read_lux(sensor: &mut Max44009<I2cdev>) -> Result<f32, Box<dyn Error>> {
+    match sensor.read_lux() {
+        Ok(lux) => Ok(lux.value()),
+        Err(max44009::Error::Overrange) => Ok(MAX_LUX),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Run the daemon until stopped.
+pub fn run(
+    sensor: &mut Max44009<I2cdev>,
+    config: &Config,
+    stop: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let interrupt = match &config.gpio {
+        Some(gpio) => {
+            let mut chip =
+                Chip::new(&gpio.chip).map_err(|e| format!("{}: {}", gpio.chip.display(), e))?;
+            let line = chip.get_line(gpio.line)?;
+            Interrupt::Gpio(line.request(LineRequestFlags::INPUT, 1, "max44009")?)
+        }
+        None => Interrupt::Status,
+    };
+    let mut previous = read_lux(sensor)?;
+    program_window(sensor, &config.triggers, previous)?;
+    sensor.set_threshold_timer((config.timer_ms / 100) as u8)?;
+    // Clear any pending interrupt.
+    sensor.has_interrupt_happened()?;
+    sensor.enable_interrupt()?;
+    eprintln!("Monitoring, current lux intensity: {} lx", previous);
+    let interval = Duration::from_millis(config.poll_interval_ms);
+    while !stop.load(Ordering::SeqCst) {
+        let pending = match &interrupt {
+            Interrupt::Status => sensor.has_interrupt_happened()?,
+            Interrupt::Gpio(handle) => {
+                handle.get_value()? == 0 && sensor.has_interrupt_happened()?
+            }
+        };
+        if pending {
+            let lux = read_lux(sensor)?;
+            for (trigger, crossing) in crossings(&config.triggers, previous, lux) {
+                fire(trigger, crossing, lux);
+            }
+            program_window(sensor, &config.triggers, lux)?;
+            previous = lux;
+        }
+        thread::sleep(interval);
+    }
+    sensor.disable_interrupt()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        address = 0x4b
+        timer_ms = 500
+
+        [gpio]
+        chip = "/dev/gpiochip0"
+        line = 17
+
+        [[trigger]]
+        name = "dark"
+        lux = 10.0
+        direction = "falling"
+        command = "echo dark"
+
+        [[trigger]]
+        name = "bright"
+        lux = 1000.0
+        fifo = "/run/max44009"
+    "#;
+
+    fn triggers() -> Vec<Trigger> {
+        toml::from_str::<Config>(CONFIG).unwrap().triggers
+    }
+
+    #[test]
+    fn can_parse_config() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(None, config.bus);
+        assert_eq!(Some(0x4B), config.address);
+        assert_eq!(100, config.poll_interval_ms);
+        assert_eq!(500, config.timer_ms);
+        assert_eq!(17, config.gpio.unwrap().line);
+        assert_eq!(2, config.triggers.len());
+        assert_eq!(Direction::Falling, config.triggers[0].direction);
+        assert_eq!(Direction::Both, config.triggers[1].direction);
+        assert_eq!(
+            Some(PathBuf::from("/run/max44009")),
+            config.triggers[1].fifo
+        );
+    }
+
+    #[test]
+    fn can_validate_config() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(Ok(()), config.validate());
+        config.timer_ms = 550;
+        assert!(config.validate().is_err());
+        config.timer_ms = 0;
+        config.triggers[0].command = None;
+        assert!(config.validate().is_err());
+        config.triggers.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(toml::from_str::<Config>("polling = 1").is_err());
+    }
+
+    #[test]
+    fn can_calculate_window() {
+        let triggers = triggers();
+        assert_eq!((0.0, 10.0), window(&triggers, 5.0));
+        assert_eq!((10.0, 1000.0), window(&triggers, 10.0));
+        assert_eq!((10.0, 1000.0), window(&triggers, 500.0));
+        assert_eq!((1000.0, MAX_LUX), window(&triggers, 2000.0));
+    }
+
+    #[test]
+    fn can_detect_crossings() {
+        let triggers = triggers();
+        let names = |previous, current| -> Vec<(&str, Crossing)> {
+            crossings(&triggers, previous, current)
+                .into_iter()
+                .map(|(t, c)| (t.name.as_str(), c))
+                .collect()
+        };
+        assert_eq!(vec![("dark", Crossing::Falling)], names(50.0, 5.0));
+        // The dark trigger only fires when falling.
+        assert!(names(5.0, 50.0).is_empty());
+        assert_eq!(vec![("bright", Crossing::Rising)], names(5.0, 2000.0));
+        assert_eq!(
+            vec![("dark", Crossing::Falling), ("bright", Crossing::Falling)],
+            names(2000.0, 5.0)
+        );
+        assert!(names(50.0, 500.0).is_empty());
+    }
+}
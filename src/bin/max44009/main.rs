@@ -0,0 +1,620 @@
+//! Command-line tool for the MAX44009 ambient light sensor on Linux.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use linux_embedded_hal::I2cdev;
+use max44009::{
+    server::Server, BusErrorKind, ConfigurationMode, CurrentDivisionRatio, Error as SensorError,
+    IntegrationTime, Lux, Max44009, MeasurementMode, SlaveAddr,
+};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+mod daemon;
+mod monitor;
+use monitor::{Format, Sample, SampleWriter, Summary};
+
+type Sensor = Max44009<I2cdev>;
+
+/// MAX44009 ambient light sensor tool
+#[derive(Debug, Parser)]
+#[command(name = "max44009", version)]
+struct Cli {
+    /// I²C bus device
+    #[arg(short, long, default_value = "/dev/i2c-1", global = true)]
+    bus: String,
+    /// Device address (0x4a or 0x4b)
+    #[arg(short, long, default_value = "0x4a", value_parser = parse_address, global = true)]
+    address: SlaveAddr,
+    /// Print the output as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Read the lux intensity
+    Read,
+    /// Set the measurement and configuration parameters
+    Configure {
+        /// Measurement mode
+        #[arg(long, value_enum)]
+        measurement_mode: Option<MeasurementModeArg>,
+        /// Configuration mode
+        #[arg(long, value_enum)]
+        configuration_mode: Option<ConfigurationModeArg>,
+        /// Integration time (only in manual configuration mode)
+        #[arg(long, value_enum)]
+        integration_time: Option<IntegrationTimeArg>,
+        /// Current division ratio (only in manual configuration mode)
+        #[arg(long, value_enum)]
+        cdr: Option<CdrArg>,
+    },
+    /// Set the interrupt thresholds in lux
+    Threshold {
+        /// Upper threshold in lux
+        #[arg(long)]
+        upper: Option<f32>,
+        /// Lower threshold in lux
+        #[arg(long)]
+        lower: Option<f32>,
+        /// Enable the interrupt
+        #[arg(long, conflicts_with = "disable_interrupt")]
+        enable_interrupt: bool,
+        /// Disable the interrupt
+        #[arg(long)]
+        disable_interrupt: bool,
+    },
+    /// Set the threshold timer
+    Timer {
+        /// Time in milliseconds (multiple of 100, up to 25500)
+        #[arg(value_parser = parse_timer)]
+        milliseconds: u8,
+    },
+    /// Print the contents of all registers
+    Dump,
+    /// Look for devices at both possible addresses
+    Probe,
+    /// Run commands or write to FIFOs when the lux intensity crosses the
+    /// levels configured in a TOML file
+    ///
+    /// Runs until interrupted.
+    Daemon {
+        /// Configuration file
+        config: PathBuf,
+    },
+    /// Serve readings and configuration changes over a Unix domain socket
+    ///
+    /// Clients send one JSON request per line and receive one JSON response
+    /// per line. Runs until interrupted.
+    Serve {
+        /// Socket path
+        #[arg(short, long, default_value = "/run/max44009.sock")]
+        socket: PathBuf,
+    },
+    /// Read the lux intensity periodically until interrupted
+    ///
+    /// A summary is printed to stderr on exit.
+    Monitor {
+        /// Time between readings in milliseconds
+        #[arg(short, long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Filter the readings
+        #[arg(long, value_enum)]
+        filter: Option<monitor::Filter>,
+        /// Smoothing factor of the exponential moving average filter (0.0 to 1.0)
+        #[arg(long, default_value_t = 0.2)]
+        alpha: f32,
+        /// Stop after this number of readings, including overrange readings
+        #[arg(short = 'n', long)]
+        count: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum MeasurementModeArg {
+    /// Measure once every 800ms
+    OnceEvery800ms,
+    /// Measure continuously
+    Continuous,
+}
+
+impl From<MeasurementModeArg> for MeasurementMode {
+    fn from(mode: MeasurementModeArg) -> Self {
+        match mode {
+            MeasurementModeArg::OnceEvery800ms => MeasurementMode::OnceEvery800ms,
+            MeasurementModeArg::Continuous => MeasurementMode::Continuous,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ConfigurationModeArg {
+    /// Integration time and CDR selected by the device
+    Automatic,
+    /// Integration time and CDR set by the user
+    Manual,
+}
+
+impl From<ConfigurationModeArg> for ConfigurationMode {
+    fn from(mode: ConfigurationModeArg) -> Self {
+        match mode {
+            ConfigurationModeArg::Automatic => ConfigurationMode::Automatic,
+            ConfigurationModeArg::Manual => ConfigurationMode::Manual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum IntegrationTimeArg {
+    #[value(name = "6.25ms")]
+    _6_25ms,
+    #[value(name = "12.5ms")]
+    _12_5ms,
+    #[value(name = "25ms")]
+    _25ms,
+    #[value(name = "50ms")]
+    _50ms,
+    #[value(name = "100ms")]
+    _100ms,
+    #[value(name = "200ms")]
+    _200ms,
+    #[value(name = "400ms")]
+    _400ms,
+    #[value(name = "800ms")]
+    _800ms,
+}
+
+impl From<IntegrationTimeArg> for IntegrationTime {
+    fn from(it: IntegrationTimeArg) -> Self {
+        match it {
+            IntegrationTimeArg::_6_25ms => IntegrationTime::_6_25ms,
+            IntegrationTimeArg::_12_5ms => IntegrationTime::_12_5ms,
+            IntegrationTimeArg::_25ms => IntegrationTime::_25ms,
+            IntegrationTimeArg::_50ms => IntegrationTime::_50ms,
+            IntegrationTimeArg::_100ms => IntegrationTime::_100ms,
+            IntegrationTimeArg::_200ms => IntegrationTime::_200ms,
+            IntegrationTimeArg::_400ms => IntegrationTime::_400ms,
+            IntegrationTimeArg::_800ms => IntegrationTime::_800ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CdrArg {
+    /// All of the photodiode current goes to the ADC
+    One,
+    /// 1/8 of the photodiode current goes to the ADC
+    OneEighth,
+}
+
+impl From<CdrArg> for CurrentDivisionRatio {
+    fn from(cdr: CdrArg) -> Self {
+        match cdr {
+            CdrArg::One => CurrentDivisionRatio::One,
+            CdrArg::OneEighth => CurrentDivisionRatio::OneEighth,
+        }
+    }
+}
+
+fn parse_address(value: &str) -> Result<SlaveAddr, String> {
+    let address = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| e.to_string())?;
+    slave_addr(address)
+}
+
+fn slave_addr(address: u8) -> Result<SlaveAddr, String> {
+    match address {
+        0x4A => Ok(SlaveAddr::Default),
+        0x4B => Ok(SlaveAddr::Alternative(true)),
+        _ => Err("the address must be 0x4a or 0x4b".to_string()),
+    }
+}
+
+fn parse_timer(value: &str) -> Result<u8, String> {
+    let milliseconds: u32 = value
+        .parse()
+        .map_err(|e: std::num::ParseIntError| e.to_string())?;
+    if milliseconds % 100 != 0 || milliseconds > 25_500 {
+        return Err("the time must be a multiple of 100 up to 25500".to_string());
+    }
+    Ok((milliseconds / 100) as u8)
+}
+
+fn address_value(address: SlaveAddr) -> u8 {
+    match address {
+        SlaveAddr::Default | SlaveAddr::Alternative(false) => 0x4A,
+        SlaveAddr::Alternative(true) => 0x4B,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Reading {
+    lux: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct Registers {
+    int_status: u8,
+    int_enable: u8,
+    configuration: u8,
+    lux_high: u8,
+    lux_low: u8,
+    upper_threshold_high: u8,
+    lower_threshold_high: u8,
+    threshold_timer: u8,
+}
+
+#[derive(Debug, Serialize)]
+struct ProbeResult {
+    address: u8,
+    found: bool,
+    /// Set if the probe failed for another reason than no device answering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn open_bus(path: &str) -> Result<I2cdev, Box<dyn Error>> {
+    I2cdev::new(path).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match &cli.command {
+        Command::Probe => return probe(&cli),
+        Command::Daemon { config } => return run_daemon(&cli, config),
+        Command::Serve { socket } => return run_server(&cli, socket),
+        _ => (),
+    }
+    let dev = open_bus(&cli.bus)?;
+    let mut sensor: Sensor = Max44009::new(dev, cli.address);
+    match cli.command {
+        Command::Read => {
+            let lux = sensor.read_lux()?;
+            if cli.json {
+                print_json(&Reading { lux: lux.value() })?;
+            } else {
+                println!("{}", lux);
+            }
+        }
+        Command::Configure {
+            measurement_mode,
+            configuration_mode,
+            integration_time,
+            cdr,
+        } => {
+            sensor.read_device_state()?;
+            if let Some(mode) = configuration_mode {
+                sensor.set_configuration_mode(mode.into())?;
+            }
+            if let Some(mode) = measurement_mode {
+                sensor.set_measurement_mode(mode.into())?;
+            }
+            if let Some(it) = integration_time {
+                sensor.set_integration_time(it.into())?;
+            }
+            if let Some(cdr) = cdr {
+                sensor.set_current_division_ratio(cdr.into())?;
+            }
+        }
+        Command::Threshold {
+            upper,
+            lower,
+            enable_interrupt,
+            disable_interrupt,
+        } => {
+            sensor.read_device_state()?;
+            if let Some(upper) = upper {
+                sensor.set_upper_threshold(Lux::new(upper))?;
+            }
+            if let Some(lower) = lower {
+                sensor.set_lower_threshold(Lux::new(lower))?;
+            }
+            if enable_interrupt {
+                sensor.enable_interrupt()?;
+            }
+            if disable_interrupt {
+                sensor.disable_interrupt()?;
+            }
+        }
+        Command::Timer { milliseconds } => {
+            sensor.set_threshold_timer(milliseconds)?;
+        }
+        Command::Dump => {
+            let r = sensor.dump_registers()?;
+            if cli.json {
+                print_json(&Registers {
+                    int_status: r[0],
+                    int_enable: r[1],
+                    configuration: r[2],
+                    lux_high: r[3],
+                    lux_low: r[4],
+                    upper_threshold_high: r[5],
+                    lower_threshold_high: r[6],
+                    threshold_timer: r[7],
+                })?;
+            } else {
+                let names = [
+                    "Interrupt status",
+                    "Interrupt enable",
+                    "Configuration",
+                    "Lux high byte",
+                    "Lux low byte",
+                    "Upper threshold high byte",
+                    "Lower threshold high byte",
+                    "Threshold timer",
+                ];
+                for (register, (name, value)) in names.iter().zip(r.iter()).enumerate() {
+                    println!(
+                        "{:#04x} {:<26} {:#04x} {:#010b}",
+                        register, name, value, value
+                    );
+                }
+            }
+        }
+        Command::Monitor {
+            interval,
+            format,
+            output,
+            filter,
+            alpha,
+            count,
+        } => {
+            let writer: Box<dyn Write> = match output {
+                Some(path) => {
+                    Box::new(File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?)
+                }
+                None => Box::new(io::stdout()),
+            };
+            let mut writer = SampleWriter::new(writer, format);
+            let mut filter = filter.map(|filter| monitor::create_filter(filter, alpha));
+            let interval = Duration::from_millis(interval);
+            let stop = stop_on_interrupt()?;
+            let mut summary = Summary::default();
+            let mut next = Instant::now();
+            // The summary is printed even if monitoring ends with an error.
+            let mut result: Result<(), Box<dyn Error>> = Ok(());
+            while !stop.load(Ordering::SeqCst) {
+                match sensor.read_lux() {
+                    Ok(lux) => {
+                        let lux = match filter.as_mut() {
+                            Some(filter) => filter.update(lux.value()),
+                            None => lux.value(),
+                        };
+                        if let Err(e) = writer.write(&Sample::now(lux)) {
+                            result = Err(e.into());
+                            break;
+                        }
+                        summary.add(lux);
+                    }
+                    Err(SensorError::Overrange) => {
+                        eprintln!("Warning: lux intensity above the measurable range");
+                        summary.add_overrange();
+                    }
+                    Err(e) => {
+                        result = Err(e.into());
+                        break;
+                    }
+                }
+                if count.is_some_and(|n| summary.count + summary.overrange >= n) {
+                    break;
+                }
+                next += interval;
+                sleep_until(next, &stop);
+            }
+            if cli.json {
+                eprintln!("{}", serde_json::to_string(&summary)?);
+            } else {
+                eprintln!("{}", summary);
+            }
+            result?;
+        }
+        Command::Probe | Command::Daemon { .. } | Command::Serve { .. } => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Return a flag set when the process is interrupted (Ctrl-C, SIGTERM).
+fn stop_on_interrupt() -> Result<Arc<AtomicBool>, Box<dyn Error>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))?;
+    Ok(stop)
+}
+
+fn run_daemon(cli: &Cli, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let config: daemon::Config =
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    config
+        .validate()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let bus = config.bus.as_deref().unwrap_or(&cli.bus);
+    let address = match config.address {
+        Some(address) => slave_addr(address).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => cli.address,
+    };
+    let mut sensor = Max44009::new(open_bus(bus)?, address);
+    let stop = stop_on_interrupt()?;
+    daemon::run(&mut sensor, &config, &stop)
+}
+
+fn run_server(cli: &Cli, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let mut sensor: Sensor = Max44009::new(open_bus(&cli.bus)?, cli.address);
+    sensor.read_device_state()?;
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{}: a server is already running", path.display()).into());
+        }
+        // Left behind by a server that did not shut down cleanly.
+        fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let listener = UnixListener::bind(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let stop = stop_on_interrupt()?;
+    let result = Server::new(sensor).run(listener, stop);
+    let _ = fs::remove_file(path);
+    result?;
+    Ok(())
+}
+
+/// Sleep until the deadline or until stopped.
+fn sleep_until(deadline: Instant, stop: &AtomicBool) {
+    const STEP: Duration = Duration::from_millis(50);
+    loop {
+        let now = Instant::now();
+        if now >= deadline || stop.load(Ordering::SeqCst) {
+            return;
+        }
+        thread::sleep((deadline - now).min(STEP));
+    }
+}
+
+fn probe(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let mut dev = open_bus(&cli.bus)?;
+    let mut results = Vec::new();
+    for address in [SlaveAddr::Default, SlaveAddr::Alternative(true)] {
+        let mut sensor = Max44009::new(dev, address);
+        let (found, error) = match sensor.read_current_division_ratio() {
+            Ok(_) => (true, None),
+            Err(e)
+                if matches!(
+                    e.bus_error_kind(),
+                    Some(BusErrorKind::AddressNack | BusErrorKind::UnknownNack)
+                ) =>
+            {
+                (false, None)
+            }
+            Err(e) => (false, Some(e.to_string())),
+        };
+        dev = sensor.destroy();
+        results.push(ProbeResult {
+            address: address_value(address),
+            found,
+            error,
+        });
+    }
+    if cli.json {
+        print_json(&results)?;
+    } else {
+        for result in &results {
+            match (&result.error, result.found) {
+                (Some(error), _) => println!("{:#04x}: error: {}", result.address, error),
+                (None, true) => println!("{:#04x}: found", result.address),
+                (None, false) => println!("{:#04x}: not found", result.address),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprint!("Error: {}", e);
+            let mut source = e.source();
+            while let Some(e) = source {
+                eprint!(": {}", e);
+                source = e.source();
+            }
+            eprintln!();
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn can_parse_address() {
+        assert_eq!(Ok(SlaveAddr::Default), parse_address("0x4a"));
+        assert_eq!(Ok(SlaveAddr::Alternative(true)), parse_address("0x4B"));
+        assert_eq!(Ok(SlaveAddr::Alternative(true)), parse_address("75"));
+        assert!(parse_address("0x4c").is_err());
+        assert!(parse_address("abc").is_err());
+    }
+
+    #[test]
+    fn can_parse_timer() {
+        assert_eq!(Ok(0), parse_timer("0"));
+        assert_eq!(Ok(5), parse_timer("500"));
+        assert_eq!(Ok(255), parse_timer("25500"));
+        assert!(parse_timer("550").is_err());
+        assert!(parse_timer("25600").is_err());
+    }
+
+    #[test]
+    fn can_parse_configure_command() {
+        let cli = Cli::try_parse_from([
+            "max44009",
+            "--bus",
+            "/dev/i2c-0",
+            "configure",
+            "--configuration-mode",
+            "manual",
+            "--integration-time",
+            "6.25ms",
+            "--json",
+        ])
+        .unwrap();
+        assert_eq!("/dev/i2c-0", cli.bus);
+        assert!(cli.json);
+        match cli.command {
+            Command::Configure {
+                configuration_mode: Some(ConfigurationModeArg::Manual),
+                integration_time: Some(IntegrationTimeArg::_6_25ms),
+                measurement_mode: None,
+                cdr: None,
+            } => (),
+            command => panic!("Unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn rejects_zero_monitor_interval() {
+        assert!(Cli::try_parse_from(["max44009", "monitor", "--interval", "0"]).is_err());
+        let cli = Cli::try_parse_from(["max44009", "monitor", "--interval", "1"]).unwrap();
+        match cli.command {
+            Command::Monitor { interval: 1, .. } => (),
+            command => panic!("Unexpected command: {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Continuous monitoring
+
+use clap::ValueEnum;
+use max44009::filter::{ExponentialMovingAverage, LuxFilter, Median, MovingAverage};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Window of the moving average and median filters.
+pub const FILTER_WINDOW: usize = 5;
+
+/// Output format
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Comma-separated values with a header
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// Reading filter
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Filter {
+    /// Moving average over the last 5 readings
+    Average,
+    /// Median of the last 5 readings
+    Median,
+    /// Exponential moving average (see --alpha)
+    Ema,
+}
+
+/// Create the filter.
+pub fn create_filter(filter: Filter, alpha: f32) -> Box<dyn LuxFilter> {
+    match filter {
+        Filter::Average => Box::new(MovingAverage::<FILTER_WINDOW>::new()),
+        Filter::Median => Box::new(Median::<FILTER_WINDOW>::new()),
+        Filter::Ema => Box::new(ExponentialMovingAverage::new(alpha)),
+    }
+}
+
+/// Timestamped reading
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Sample {
+    /// Seconds since the Unix epoch.
+    pub timestamp: f64,
+    /// Lux intensity.
+    pub lux: f32,
+}
+
+impl Sample {
+    /// Create a sample taken now.
+    pub fn now(lux: f32) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        Sample { timestamp, lux }
+    }
+}
+
+/// Writes samples in the selected format.
+pub struct SampleWriter<W> {
+    writer: W,
+    format: Format,
+    header_written: bool,
+}
+
+impl<W: Write> SampleWriter<W> {
+    /// Create a new sample writer.
+    pub fn new(writer: W, format: Format) -> Self {
+        SampleWriter {
+            writer,
+            format,
+            header_written: false,
+        }
+    }
+
+    /// Write a sample and flush it.
+    pub fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        match self.format {
+            Format::Csv => {
+                if !self.header_written {
+                    writeln!(self.writer, "timestamp,lux")?;
+                    self.header_written = true;
+                }
+                writeln!(self.writer, "{:.3},{}", sample.timestamp, sample.lux)?;
+            }
+            Format::Jsonl => {
+                serde_json::to_writer(&mut self.writer, sample)?;
+                writeln!(self.writer)?;
+            }
+        }
+        self.writer.flush()
+    }
+
+    /// Return the inner writer.
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Minimum, maximum and mean of the readings
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Summary {
+    /// Number of readings.
+    pub count: u64,
+    /// Minimum lux intensity.
+    pub min: Option<f32>,
+    /// Maximum lux intensity.
+    pub max: Option<f32>,
+    /// Mean lux intensity.
+    pub mean: Option<f32>,
+    /// Number of overrange readings, which are not included.
+    pub overrange: u64,
+    #[serde(skip)]
+    sum: f64,
+}
+
+impl Summary {
+    /// Add a reading.
+    pub fn add(&mut self, lux: f32) {
+        self.count += 1;
+        self.sum += f64::from(lux);
+        self.min = Some(self.min.map_or(lux, |min| min.min(lux)));
+        self.max = Some(self.max.map_or(lux, |max| max.max(lux)));
+        self.mean = Some((self.sum / self.count as f64) as f32);
+    }
+
+    /// Count an overrange reading.
+    pub fn add_overrange(&mut self) {
+        self.overrange += 1;
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} readings", self.count)?;
+        if let (Some(min), Some(max), Some(mean)) = (self.min, self.max, self.mean) {
+            write!(f, ", min: {} lx, max: {} lx, mean: {} lx", min, max, mean)?;
+        }
+        if self.overrange != 0 {
+            write!(f, ", {} overrange", self.overrange)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_all(format: Format) -> String {
+        let mut writer = SampleWriter::new(Vec::new(), format);
+        for (timestamp, lux) in [(1.5, 10.0), (2.0, 20.5)] {
+            writer.write(&Sample { timestamp, lux }).unwrap();
+        }
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn can_write_csv() {
+        assert_eq!(
+            "timestamp,lux\n1.500,10\n2.000,20.5\n",
+            write_all(Format::Csv)
+        );
+    }
+
+    #[test]
+    fn can_write_json_lines() {
+        assert_eq!(
+            "{\"timestamp\":1.5,\"lux\":10.0}\n{\"timestamp\":2.0,\"lux\":20.5}\n",
+            write_all(Format::Jsonl)
+        );
+    }
+
+    #[test]
+    fn can_summarize() {
+        let mut summary = Summary::default();
+        assert_eq!("0 readings", summary.to_string());
+        for lux in [10.0, 30.0, 20.0] {
+            summary.add(lux);
+        }
+        summary.add_overrange();
+        assert_eq!(3, summary.count);
+        assert_eq!(Some(10.0), summary.min);
+        assert_eq!(Some(30.0), summary.max);
+        assert_eq!(Some(20.0), summary.mean);
+        assert_eq!(
+            "3 readings, min: 10 lx, max: 30 lx, mean: 20 lx, 1 overrange",
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn can_filter() {
+        let mut filter = create_filter(Filter::Median, 0.0);
+        for lux in [10.0, 1000.0] {
+            filter.update(lux);
+        }
+        assert_eq!(11.0, filter.update(11.0));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::BusRecoveryError;
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::{InputPin, OutputPin},
+};
+
+/// Half of the SCL period in microseconds. (100kHz)
+const HALF_CLOCK_PERIOD_US: u32 = 5;
+
+/// Maximum number of SCL pulses needed for a slave to release SDA.
+///
+/// A slave in the middle of a read transaction releases SDA at the latest
+/// after the remaining bits of the current byte plus the acknowledge bit.
+const MAX_CLOCK_PULSES: u8 = 9;
+
+/// Recover an I²C bus where a slave device holds SDA low.
+///
+/// If the MCU is reset in the middle of a transaction, the device may still
+/// be driving SDA low while waiting for clock pulses. Every subsequent
+/// transaction then fails. This clocks SCL until the device releases SDA
+/// (at most 9 pulses) and then issues a STOP condition.
+///
+/// The I²C peripheral must not be driving the pins during recovery so the
+/// pins need to be released (e.g. with `Max44009::destroy()`) and configured
+/// as open-drain GPIO pins. Afterwards the I²C peripheral can be set up
+/// again and a new driver instance created.
+///
+/// Returns `BusRecoveryError::SdaStuckLow` if SDA is still low after all
+/// clock pulses.
+pub fn recover_bus<SCL, SDA, D, E>(
+    scl: &mut SCL,
+    sda: &mut SDA,
+    delay: &mut D,
+) -> Result<(), BusRecoveryError<E>>
+where
+    SCL: OutputPin<Error = E>,
+    SDA: InputPin<Error = E> + OutputPin<Error = E>,
+    D: DelayUs<u32>,
+{
+    sda.set_high().map_err(BusRecoveryError::Pin)?;
+    let mut pulses = 0;
+    while sda.is_low().map_err(BusRecoveryError::Pin)? {
+        if pulses == MAX_CLOCK_PULSES {
+            return Err(BusRecoveryError::SdaStuckLow);
+        }
+        scl.set_low().map_err(BusRecoveryError::Pin)?;
+        delay.delay_us(HALF_CLOCK_PERIOD_US);
+        scl.set_high().map_err(BusRecoveryError::Pin)?;
+        delay.delay_us(HALF_CLOCK_PERIOD_US);
+        pulses += 1;
+    }
+    // STOP condition: SDA rising while SCL is high.
+    scl.set_low().map_err(BusRecoveryError::Pin)?;
+    delay.delay_us(HALF_CLOCK_PERIOD_US);
+    sda.set_low().map_err(BusRecoveryError::Pin)?;
+    delay.delay_us(HALF_CLOCK_PERIOD_US);
+    scl.set_high().map_err(BusRecoveryError::Pin)?;
+    delay.delay_us(HALF_CLOCK_PERIOD_US);
+    sda.set_high().map_err(BusRecoveryError::Pin)?;
+    delay.delay_us(HALF_CLOCK_PERIOD_US);
+    Ok(())
+}
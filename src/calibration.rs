@@ -0,0 +1,621 @@
+//! Calibration correction
+
+use crate::{
+    configuration::{convert_from_threshold, convert_to_threshold},
+    Error, Lux, Max44009, Register,
+};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Maximum number of points in a piecewise-linear calibration table.
+pub const MAX_CALIBRATION_POINTS: usize = 8;
+
+/// Size of a calibration record in bytes. See `Calibration::to_bytes()`.
+pub const CALIBRATION_RECORD_SIZE: usize = 9 + 8 * MAX_CALIBRATION_POINTS;
+
+/// Calibration correction applied to the lux readings
+///
+/// The correction maps a measured lux intensity to the actual lux
+/// intensity. First the gain and offset are applied:
+/// `actual = gain * measured + offset`. Then, if a table of at least two
+/// points is set, the result is corrected by linear interpolation between
+/// the table points. Beyond the first and last points, the first and last
+/// segments are extrapolated.
+///
+/// Corrected values below 0 lux are reported as 0 lux.
+///
+/// The default calibration does not change the readings.
+///
+/// With the `serde` feature, only the table points in use are serialized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawCalibration"))]
+pub struct Calibration {
+    gain: f32,
+    offset: Lux,
+    table: [(Lux, Lux); MAX_CALIBRATION_POINTS],
+    table_len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Calibration {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Calibration", 3)?;
+        state.serialize_field("gain", &self.gain)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field("table", self.table())?;
+        state.end()
+    }
+}
+
+/// Calibration as deserialized, before validation.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Calibration")]
+struct RawCalibration {
+    gain: f32,
+    offset: Lux,
+    #[serde(default)]
+    table: RawTable,
+}
+
+/// Calibration table of up to `MAX_CALIBRATION_POINTS` points.
+#[cfg(feature = "serde")]
+#[derive(Default)]
+struct RawTable {
+    points: [(Lux, Lux); MAX_CALIBRATION_POINTS],
+    len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RawTable {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TableVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for TableVisitor {
+            type Value = RawTable;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(
+                    f,
+                    "a sequence of at most {} (measured, actual) points",
+                    MAX_CALIBRATION_POINTS
+                )
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<RawTable, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut table = RawTable::default();
+                while let Some(point) = seq.next_element()? {
+                    if table.len == MAX_CALIBRATION_POINTS {
+                        return Err(serde::de::Error::invalid_length(table.len + 1, &self));
+                    }
+                    table.points[table.len] = point;
+                    table.len += 1;
+                }
+                Ok(table)
+            }
+        }
+
+        deserializer.deserialize_seq(TableVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl core::convert::TryFrom<RawCalibration> for Calibration {
+    type Error = &'static str;
+
+    fn try_from(raw: RawCalibration) -> Result<Self, Self::Error> {
+        let calibration =
+            Calibration::new(raw.gain, raw.offset).with_table(&raw.table.points[..raw.table.len]);
+        if calibration.is_valid() {
+            Ok(calibration)
+        } else {
+            Err("invalid calibration")
+        }
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::new(1.0, Lux::new(0.0))
+    }
+}
+
+impl Calibration {
+    /// Create a new linear calibration.
+    pub const fn new(gain: f32, offset: Lux) -> Self {
+        Calibration {
+            gain,
+            offset,
+            table: [(Lux::new(0.0), Lux::new(0.0)); MAX_CALIBRATION_POINTS],
+            table_len: 0,
+        }
+    }
+
+    /// Create a calibration compensating a cover glass with the given
+    /// transmittance (0.0 to 1.0).
+    ///
+    /// For example, a glass letting through 40% of the light has a
+    /// transmittance of 0.4.
+    pub fn from_transmittance(transmittance: f32) -> Self {
+        Calibration::new(1.0 / transmittance, Lux::new(0.0))
+    }
+
+    /// Set a piecewise-linear correction table of `(measured, actual)` points.
+    ///
+    /// The points must be sorted with both the measured and the actual
+    /// values strictly increasing so that the correction can be inverted.
+    ///
+    /// Panics if the table contains more than `MAX_CALIBRATION_POINTS` points.
+    pub fn with_table(mut self, points: &[(Lux, Lux)]) -> Self {
+        assert!(
+            points.len() <= MAX_CALIBRATION_POINTS,
+            "The calibration table can contain at most MAX_CALIBRATION_POINTS points."
+        );
+        self.table[..points.len()].copy_from_slice(points);
+        self.table_len = points.len();
+        self
+    }
+
+    /// Gain.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Offset.
+    pub fn offset(&self) -> Lux {
+        self.offset
+    }
+
+    /// Piecewise-linear correction table of `(measured, actual)` points.
+    pub fn table(&self) -> &[(Lux, Lux)] {
+        &self.table[..self.table_len]
+    }
+
+    /// Whether the calibration can be applied and inverted.
+    ///
+    /// The gain must be positive and finite, and the table points must be
+    /// finite and strictly increasing.
+    pub fn is_valid(&self) -> bool {
+        let table = self.table();
+        self.gain.is_finite()
+            && self.gain > 0.0
+            && self.offset.value().is_finite()
+            && table
+                .iter()
+                .all(|(m, a)| m.value().is_finite() && a.value().is_finite())
+            && table.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1)
+    }
+
+    /// Encode the calibration as a record to store it.
+    ///
+    /// The record contains the gain, the offset, the number of table points
+    /// and the table points. All values are little-endian `f32`.
+    pub fn to_bytes(&self) -> [u8; CALIBRATION_RECORD_SIZE] {
+        let mut bytes = [0; CALIBRATION_RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&self.gain.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.offset.value().to_le_bytes());
+        bytes[8] = self.table_len as u8;
+        for (i, (measured, actual)) in self.table().iter().enumerate() {
+            let start = 9 + 8 * i;
+            bytes[start..start + 4].copy_from_slice(&measured.value().to_le_bytes());
+            bytes[start + 4..start + 8].copy_from_slice(&actual.value().to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decode a calibration record created with `to_bytes()`.
+    ///
+    /// Returns `None` if the record is malformed or the calibration is not
+    /// valid.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != CALIBRATION_RECORD_SIZE {
+            return None;
+        }
+        let read = |start: usize| {
+            let mut value = [0; 4];
+            value.copy_from_slice(&bytes[start..start + 4]);
+            f32::from_le_bytes(value)
+        };
+        let table_len = usize::from(bytes[8]);
+        if table_len > MAX_CALIBRATION_POINTS {
+            return None;
+        }
+        let mut calibration = Calibration::new(read(0), Lux::new(read(4)));
+        for i in 0..table_len {
+            let start = 9 + 8 * i;
+            calibration.table[i] = (Lux::new(read(start)), Lux::new(read(start + 4)));
+        }
+        calibration.table_len = table_len;
+        if calibration.is_valid() {
+            Some(calibration)
+        } else {
+            None
+        }
+    }
+
+    /// Correct a measured lux intensity.
+    pub fn apply(&self, measured: Lux) -> Lux {
+        let linear = self.gain * measured.value() + self.offset.value();
+        let actual = interpolate(self.table(), linear, |p| p.0.value(), |p| p.1.value());
+        Lux::new(actual.max(0.0))
+    }
+
+    /// Compute the lux intensity the device measures for an actual lux
+    /// intensity.
+    ///
+    /// This is the inverse of `apply()`.
+    pub fn invert(&self, actual: Lux) -> Lux {
+        let linear = interpolate(
+            self.table(),
+            actual.value(),
+            |p| p.1.value(),
+            |p| p.0.value(),
+        );
+        Lux::new(((linear - self.offset.value()) / self.gain).max(0.0))
+    }
+}
+
+fn interpolate(
+    table: &[(Lux, Lux)],
+    x: f32,
+    from: impl Fn(&(Lux, Lux)) -> f32,
+    to: impl Fn(&(Lux, Lux)) -> f32,
+) -> f32 {
+    if table.len() < 2 {
+        return x;
+    }
+    let segment = table
+        .windows(2)
+        .position(|w| x < from(&w[1]))
+        .unwrap_or(table.len() - 2);
+    let (p0, p1) = (&table[segment], &table[segment + 1]);
+    let slope = (to(p1) - to(p0)) / (from(p1) - from(p0));
+    to(p0) + (x - from(p0)) * slope
+}
+
+/// Guided calibration procedure
+///
+/// For each reference illuminance, place the sensor under the reference
+/// light and call `measure()` with the actual lux intensity, e.g. as
+/// measured by a reference luxmeter. The uncalibrated readings are averaged.
+/// Once at least two reference points are measured, compute a linear or a
+/// piecewise-linear calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CalibrationProcedure {
+    points: [(Lux, Lux); MAX_CALIBRATION_POINTS],
+    len: usize,
+    samples: u8,
+    sample_interval_us: u32,
+}
+
+impl CalibrationProcedure {
+    /// Create a new calibration procedure.
+    ///
+    /// `samples` readings are averaged for each reference point, waiting
+    /// `sample_interval_us` between readings. The interval should be at
+    /// least the integration time so that each reading is a new measurement.
+    pub fn new(samples: u8, sample_interval_us: u32) -> Self {
+        CalibrationProcedure {
+            points: [(Lux::new(0.0), Lux::new(0.0)); MAX_CALIBRATION_POINTS],
+            len: 0,
+            samples: samples.max(1),
+            sample_interval_us,
+        }
+    }
+
+    /// Measure a reference point.
+    ///
+    /// Returns the average uncalibrated reading.
+    ///
+    /// Panics if `MAX_CALIBRATION_POINTS` points have already been measured.
+    pub fn measure<I2C, D, E, DELAY>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        delay: &mut DELAY,
+        reference: Lux,
+    ) -> Result<Lux, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+        DELAY: DelayUs<u32>,
+    {
+        let mut sum = Lux::new(0.0);
+        for i in 0..self.samples {
+            if i != 0 {
+                delay.delay_us(self.sample_interval_us);
+            }
+            sum += sensor.read_uncalibrated_lux()?;
+        }
+        let measured = sum / f32::from(self.samples);
+        self.add_point(measured, reference);
+        Ok(measured)
+    }
+
+    /// Add a reference point measured otherwise.
+    ///
+    /// Panics if `MAX_CALIBRATION_POINTS` points have already been added.
+    pub fn add_point(&mut self, measured: Lux, reference: Lux) {
+        assert!(
+            self.len < MAX_CALIBRATION_POINTS,
+            "At most MAX_CALIBRATION_POINTS reference points can be measured."
+        );
+        self.points[self.len] = (measured, reference);
+        self.len += 1;
+    }
+
+    /// Reference points as `(measured, reference)` pairs.
+    pub fn points(&self) -> &[(Lux, Lux)] {
+        &self.points[..self.len]
+    }
+
+    /// Compute the gain and offset fitting the reference points best
+    /// (least squares).
+    ///
+    /// With two points, the line goes through both. Returns `None` if fewer
+    /// than two distinct points were measured or the result is not valid.
+    pub fn linear(&self) -> Option<Calibration> {
+        let points = self.points();
+        if points.len() < 2 {
+            return None;
+        }
+        let n = points.len() as f32;
+        let mean_x = points.iter().map(|p| p.0.value()).sum::<f32>() / n;
+        let mean_y = points.iter().map(|p| p.1.value()).sum::<f32>() / n;
+        let (mut sxy, mut sxx) = (0.0, 0.0);
+        for (x, y) in points.iter() {
+            sxy += (x.value() - mean_x) * (y.value() - mean_y);
+            sxx += (x.value() - mean_x) * (x.value() - mean_x);
+        }
+        if sxx == 0.0 {
+            return None;
+        }
+        let gain = sxy / sxx;
+        let calibration = Calibration::new(gain, Lux::new(mean_y - gain * mean_x));
+        Some(calibration).filter(Calibration::is_valid)
+    }
+
+    /// Compute a piecewise-linear calibration going through all reference
+    /// points.
+    ///
+    /// Returns `None` if fewer than two points were measured or the
+    /// measured and reference values do not both increase.
+    pub fn piecewise(&self) -> Option<Calibration> {
+        let mut points = self.points;
+        let points = &mut points[..self.len];
+        if points.len() < 2 {
+            return None;
+        }
+        points.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(core::cmp::Ordering::Equal));
+        let calibration = Calibration::default().with_table(points);
+        Some(calibration).filter(Calibration::is_valid)
+    }
+
+    /// Discard all reference points.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Set the calibration correction.
+    ///
+    /// The correction is applied to every lux reading. The lux values passed
+    /// to the threshold setters are converted back so that the thresholds
+    /// compare against the corrected lux intensity.
+    ///
+    /// Thresholds that were already set are converted again with the new
+    /// calibration and written to the device. Thresholds not set through
+    /// this driver are converted from their register value with the
+    /// previous calibration, unless they are outside of the measurable range
+    /// like the power-on default upper threshold.
+    ///
+    /// Returns `Error::InvalidCalibration` if the calibration is not valid.
+    /// See `Calibration::is_valid()`. Returns `Error::InvalidThreshold` if
+    /// a threshold is outside of the measurable range with the new
+    /// calibration. In both cases nothing is changed. If writing the lower
+    /// threshold fails after the upper threshold was written, the previous
+    /// upper threshold is written back so that the device and the
+    /// calibration stay consistent. If writing a threshold fails, the
+    /// calibration is not changed.
+    pub fn set_calibration(&mut self, calibration: Calibration) -> Result<(), Error<E>> {
+        if !calibration.is_valid() {
+            return Err(Error::InvalidCalibration);
+        }
+        let upper = self.recalibrated_threshold(
+            &calibration,
+            self.upper_threshold_lux,
+            self.upper_threshold,
+        )?;
+        let lower = self.recalibrated_threshold(
+            &calibration,
+            self.lower_threshold_lux,
+            self.lower_threshold,
+        )?;
+        let previous_upper = self.upper_threshold;
+        if upper != previous_upper {
+            self.write_register(Register::UPPER_THRESHOLD_HIGH, upper)?;
+            self.upper_threshold = upper;
+        }
+        if lower != self.lower_threshold {
+            if let Err(e) = self.write_register(Register::LOWER_THRESHOLD_HIGH, lower) {
+                if upper != previous_upper {
+                    self.write_register(Register::UPPER_THRESHOLD_HIGH, previous_upper)?;
+                    self.upper_threshold = previous_upper;
+                }
+                return Err(e);
+            }
+            self.lower_threshold = lower;
+        }
+        self.calibration = calibration;
+        Ok(())
+    }
+
+    /// Threshold register value for the calibration.
+    fn recalibrated_threshold(
+        &self,
+        calibration: &Calibration,
+        lux: Option<Lux>,
+        threshold: u8,
+    ) -> Result<u8, Error<E>> {
+        match lux {
+            Some(lux) => convert_to_threshold(calibration.invert(lux).value()),
+            None => {
+                let lux = convert_from_threshold(threshold);
+                if convert_to_threshold::<E>(lux).is_err() {
+                    // Outside of the measurable range, e.g. the power-on default.
+                    return Ok(threshold);
+                }
+                let lux = self.calibration.apply(Lux::new(lux));
+                convert_to_threshold(calibration.invert(lux).value())
+            }
+        }
+    }
+
+    /// Current calibration correction.
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    fn table() -> [(Lux, Lux); 3] {
+        [
+            (Lux::new(10.0), Lux::new(20.0)),
+            (Lux::new(100.0), Lux::new(150.0)),
+            (Lux::new(1000.0), Lux::new(1200.0)),
+        ]
+    }
+
+    #[test]
+    fn default_does_not_change_readings() {
+        let calibration = Calibration::default();
+        assert_eq!(Lux::new(123.0), calibration.apply(Lux::new(123.0)));
+        assert_eq!(Lux::new(123.0), calibration.invert(Lux::new(123.0)));
+    }
+
+    #[test]
+    fn can_apply_gain_and_offset() {
+        let calibration = Calibration::new(2.0, Lux::new(1.0));
+        assert_near(21.0, calibration.apply(Lux::new(10.0)).value(), 0.001);
+        assert_near(10.0, calibration.invert(Lux::new(21.0)).value(), 0.001);
+    }
+
+    #[test]
+    fn can_compensate_transmittance() {
+        let calibration = Calibration::from_transmittance(0.4);
+        assert_near(250.0, calibration.apply(Lux::new(100.0)).value(), 0.01);
+    }
+
+    #[test]
+    fn negative_values_are_clamped() {
+        let calibration = Calibration::new(1.0, Lux::new(-5.0));
+        assert_eq!(Lux::new(0.0), calibration.apply(Lux::new(1.0)));
+    }
+
+    #[test]
+    fn can_interpolate_table() {
+        let calibration = Calibration::default().with_table(&table());
+        assert_near(85.0, calibration.apply(Lux::new(55.0)).value(), 0.001);
+        assert_near(150.0, calibration.apply(Lux::new(100.0)).value(), 0.001);
+        assert_near(55.0, calibration.invert(Lux::new(85.0)).value(), 0.001);
+    }
+
+    #[test]
+    fn can_extrapolate_table() {
+        let calibration = Calibration::default().with_table(&table());
+        assert_near(12.778, calibration.apply(Lux::new(5.0)).value(), 0.001);
+        assert_near(2366.667, calibration.apply(Lux::new(2000.0)).value(), 0.01);
+        assert_near(2000.0, calibration.invert(Lux::new(2366.667)).value(), 0.01);
+    }
+
+    #[test]
+    fn can_validate() {
+        assert!(Calibration::default().with_table(&table()).is_valid());
+        assert!(!Calibration::new(0.0, Lux::new(0.0)).is_valid());
+        assert!(!Calibration::new(f32::NAN, Lux::new(0.0)).is_valid());
+        let unsorted = [table()[1], table()[0]];
+        assert!(!Calibration::default().with_table(&unsorted).is_valid());
+    }
+
+    #[test]
+    fn can_encode_and_decode() {
+        let calibration = Calibration::new(2.5, Lux::new(-1.0)).with_table(&table());
+        let bytes = calibration.to_bytes();
+        assert_eq!(Some(calibration), Calibration::from_bytes(&bytes));
+        assert_eq!(None, Calibration::from_bytes(&bytes[1..]));
+    }
+
+    #[test]
+    fn decoding_rejects_invalid_records() {
+        let mut bytes = Calibration::default().to_bytes();
+        bytes[8] = MAX_CALIBRATION_POINTS as u8 + 1;
+        assert_eq!(None, Calibration::from_bytes(&bytes));
+        let bytes = Calibration::new(0.0, Lux::new(0.0)).to_bytes();
+        assert_eq!(None, Calibration::from_bytes(&bytes));
+    }
+
+    #[test]
+    fn procedure_computes_two_point_calibration() {
+        let mut procedure = CalibrationProcedure::new(1, 0);
+        procedure.add_point(Lux::new(40.0), Lux::new(100.0));
+        procedure.add_point(Lux::new(400.0), Lux::new(1000.0));
+        let calibration = procedure.linear().unwrap();
+        assert_near(2.5, calibration.gain(), 0.001);
+        assert_near(0.0, calibration.offset().value(), 0.01);
+    }
+
+    #[test]
+    fn procedure_fits_multiple_points() {
+        let mut procedure = CalibrationProcedure::new(1, 0);
+        procedure.add_point(Lux::new(10.0), Lux::new(21.0));
+        procedure.add_point(Lux::new(20.0), Lux::new(41.0));
+        procedure.add_point(Lux::new(30.0), Lux::new(61.0));
+        let calibration = procedure.linear().unwrap();
+        assert_near(2.0, calibration.gain(), 0.001);
+        assert_near(1.0, calibration.offset().value(), 0.01);
+    }
+
+    #[test]
+    fn procedure_computes_piecewise_calibration() {
+        let mut procedure = CalibrationProcedure::new(1, 0);
+        procedure.add_point(Lux::new(100.0), Lux::new(150.0));
+        procedure.add_point(Lux::new(10.0), Lux::new(20.0));
+        let calibration = procedure.piecewise().unwrap();
+        assert_eq!(&table()[..2], calibration.table());
+    }
+
+    #[test]
+    fn procedure_needs_two_points() {
+        let mut procedure = CalibrationProcedure::new(1, 0);
+        procedure.add_point(Lux::new(10.0), Lux::new(20.0));
+        assert_eq!(None, procedure.linear());
+        assert_eq!(None, procedure.piecewise());
+        procedure.add_point(Lux::new(10.0), Lux::new(30.0));
+        assert_eq!(None, procedure.linear());
+        assert_eq!(None, procedure.piecewise());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_points_panics() {
+        let points = [(Lux::new(0.0), Lux::new(0.0)); MAX_CALIBRATION_POINTS + 1];
+        let _ = Calibration::default().with_table(&points);
+    }
+}
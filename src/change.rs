@@ -0,0 +1,232 @@
+//! Rate-of-change detection
+//!
+//! Detects sudden drops and rises of the lux intensity, e.g. a shadow cast
+//! by a person passing by the sensor. The relative change between successive
+//! readings is compared against configurable thresholds, so the time between
+//! readings defines how sudden a change must be.
+//!
+//! For fast reaction times, use continuous measurement mode with a short
+//! integration time in manual configuration mode.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Rate-of-change detector configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangeDetectorConfig {
+    /// Relative drop between successive readings that triggers a
+    /// `SuddenDrop` event.
+    ///
+    /// For example, 0.3 triggers when a reading is at least 30% lower than
+    /// the previous one. Must be below 1.0 to be reachable at all.
+    pub drop: f32,
+    /// Relative rise between successive readings that triggers a
+    /// `SuddenRise` event.
+    pub rise: f32,
+    /// Relative changes are computed against at least this lux intensity so
+    /// that noise in the dark does not trigger events.
+    pub min_lux: Lux,
+    /// Time after an event during which no further events are emitted.
+    pub hold_off_ms: u32,
+}
+
+impl Default for ChangeDetectorConfig {
+    /// 30% drop, 30% rise, 10 lux minimum and 1 second hold-off.
+    ///
+    /// Above 10 lux one step of the reading is below 1% of the intensity, so
+    /// quantization noise stays well below the thresholds even at the 6.25ms
+    /// cadence of continuous mode.
+    fn default() -> Self {
+        ChangeDetectorConfig {
+            drop: 0.3,
+            rise: 0.3,
+            min_lux: Lux::new(10.0),
+            hold_off_ms: 1_000,
+        }
+    }
+}
+
+/// Sudden change event
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChangeEvent {
+    /// The lux intensity dropped. Contains the relative change.
+    SuddenDrop(f32),
+    /// The lux intensity rose. Contains the relative change.
+    SuddenRise(f32),
+}
+
+/// Rate-of-change detector
+///
+/// Timestamps are in milliseconds from an arbitrary monotonic clock and may
+/// wrap around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChangeDetector {
+    config: ChangeDetectorConfig,
+    last: Option<Lux>,
+    last_event_ms: Option<u32>,
+    change: Option<f32>,
+}
+
+impl ChangeDetector {
+    /// Create a new detector.
+    pub fn new(config: ChangeDetectorConfig) -> Self {
+        ChangeDetector {
+            config,
+            last: None,
+            last_event_ms: None,
+            change: None,
+        }
+    }
+
+    /// Relative change between the last two readings.
+    pub fn change(&self) -> Option<f32> {
+        self.change
+    }
+
+    /// Process a lux reading taken at `timestamp_ms`.
+    ///
+    /// Returns an event if the relative change exceeds a threshold.
+    pub fn update(&mut self, lux: Lux, timestamp_ms: u32) -> Option<ChangeEvent> {
+        let last_lux = self.last.replace(lux)?;
+        let reference = if last_lux > self.config.min_lux {
+            last_lux
+        } else {
+            self.config.min_lux
+        };
+        let change = (lux - last_lux) / reference;
+        self.change = Some(change);
+        if let Some(last_event) = self.last_event_ms {
+            if timestamp_ms.wrapping_sub(last_event) < self.config.hold_off_ms {
+                return None;
+            }
+        }
+        let event = if change <= -self.config.drop {
+            ChangeEvent::SuddenDrop(change)
+        } else if change >= self.config.rise {
+            ChangeEvent::SuddenRise(change)
+        } else {
+            return None;
+        };
+        self.last_event_ms = Some(timestamp_ms);
+        Some(event)
+    }
+
+    /// Read the lux intensity from the device and process it.
+    pub fn poll<I2C, D, E>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        now_ms: u32,
+    ) -> Result<Option<ChangeEvent>, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let lux = sensor.read_lux()?;
+        Ok(self.update(lux, now_ms))
+    }
+
+    /// Discard all previous readings.
+    pub fn reset(&mut self) {
+        *self = ChangeDetector::new(self.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new() -> ChangeDetector {
+        ChangeDetector::new(ChangeDetectorConfig::default())
+    }
+
+    #[test]
+    fn first_reading_has_no_change() {
+        let mut detector = new();
+        assert_eq!(None, detector.update(Lux::new(100.0), 0));
+        assert_eq!(None, detector.change());
+    }
+
+    #[test]
+    fn detects_sudden_drop() {
+        let mut detector = new();
+        detector.update(Lux::new(100.0), 0);
+        assert_eq!(
+            Some(ChangeEvent::SuddenDrop(-0.5)),
+            detector.update(Lux::new(50.0), 100)
+        );
+    }
+
+    #[test]
+    fn detects_sudden_rise() {
+        let mut detector = new();
+        detector.update(Lux::new(100.0), 0);
+        assert_eq!(
+            Some(ChangeEvent::SuddenRise(0.5)),
+            detector.update(Lux::new(150.0), 100)
+        );
+    }
+
+    #[test]
+    fn ignores_gradual_changes() {
+        let mut detector = new();
+        detector.update(Lux::new(100.0), 0);
+        assert_eq!(None, detector.update(Lux::new(80.0), 100));
+        assert_eq!(Some(-0.2), detector.change());
+        assert_eq!(None, detector.update(Lux::new(64.0), 200));
+    }
+
+    #[test]
+    fn detects_drop_at_once_every_800ms_cadence() {
+        let mut detector = new();
+        detector.update(Lux::new(100.0), 0);
+        assert_eq!(None, detector.update(Lux::new(80.0), 800));
+        assert_eq!(
+            Some(ChangeEvent::SuddenDrop(-0.5)),
+            detector.update(Lux::new(40.0), 1_600)
+        );
+        detector.update(Lux::new(100.0), 2_400);
+        assert_eq!(
+            Some(ChangeEvent::SuddenDrop(-1.0)),
+            detector.update(Lux::new(0.0), 3_200)
+        );
+    }
+
+    #[test]
+    fn ignores_quantization_noise_at_continuous_cadence() {
+        let mut detector = new();
+        let mut timestamp = 0;
+        for counts in [223, 222, 224, 222, 223, 224, 222] {
+            let lux = Lux::new(counts as f32 * 0.045);
+            assert_eq!(None, detector.update(lux, timestamp));
+            timestamp += 25;
+        }
+        detector.reset();
+        for counts in [129, 128, 129, 130, 128] {
+            let lux = Lux::new((counts << 8) as f32 * 0.045);
+            assert_eq!(None, detector.update(lux, timestamp));
+            timestamp += 25;
+        }
+    }
+
+    #[test]
+    fn uses_minimum_lux_as_reference() {
+        let mut detector = new();
+        detector.update(Lux::new(0.045), 0);
+        assert_eq!(None, detector.update(Lux::new(2.0), 100));
+        assert!((detector.change().unwrap() - 0.1955).abs() < 1e-6);
+    }
+
+    #[test]
+    fn holds_off_after_event() {
+        let mut detector = new();
+        detector.update(Lux::new(100.0), 0);
+        assert!(detector.update(Lux::new(50.0), 100).is_some());
+        assert_eq!(None, detector.update(Lux::new(20.0), 200));
+        assert!(detector.update(Lux::new(100.0), 1_100).is_some());
+    }
+}
@@ -0,0 +1,295 @@
+//! Ambient light level classification
+//!
+//! Classifies lux readings into ambient light levels with configurable band
+//! boundaries. A relative hysteresis around each boundary avoids chattering
+//! when the lux intensity hovers around it.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Ambient light level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LightLevel {
+    /// Dark. (e.g. night)
+    Dark,
+    /// Dim. (e.g. twilight, dimly lit room)
+    Dim,
+    /// Indoor lighting. (e.g. office, living room)
+    Indoor,
+    /// Overcast daylight.
+    Overcast,
+    /// Full daylight.
+    Daylight,
+    /// Direct sunlight.
+    DirectSun,
+}
+
+const LEVELS: [LightLevel; 6] = [
+    LightLevel::Dark,
+    LightLevel::Dim,
+    LightLevel::Indoor,
+    LightLevel::Overcast,
+    LightLevel::Daylight,
+    LightLevel::DirectSun,
+];
+
+/// Lower boundaries of the light levels
+///
+/// Readings below `dim` are classified as `LightLevel::Dark`. The boundaries
+/// must be in ascending order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightLevelBands {
+    /// Lower boundary of `LightLevel::Dim`.
+    pub dim: Lux,
+    /// Lower boundary of `LightLevel::Indoor`.
+    pub indoor: Lux,
+    /// Lower boundary of `LightLevel::Overcast`.
+    pub overcast: Lux,
+    /// Lower boundary of `LightLevel::Daylight`.
+    pub daylight: Lux,
+    /// Lower boundary of `LightLevel::DirectSun`.
+    pub direct_sun: Lux,
+}
+
+impl Default for LightLevelBands {
+    /// 10 lux, 100 lux, 1000 lux, 10000 lux and 32000 lux.
+    fn default() -> Self {
+        LightLevelBands {
+            dim: Lux::new(10.0),
+            indoor: Lux::new(100.0),
+            overcast: Lux::new(1_000.0),
+            daylight: Lux::new(10_000.0),
+            direct_sun: Lux::new(32_000.0),
+        }
+    }
+}
+
+impl LightLevelBands {
+    fn lower_boundary(&self, level: LightLevel) -> Lux {
+        match level {
+            LightLevel::Dark => Lux::new(0.0),
+            LightLevel::Dim => self.dim,
+            LightLevel::Indoor => self.indoor,
+            LightLevel::Overcast => self.overcast,
+            LightLevel::Daylight => self.daylight,
+            LightLevel::DirectSun => self.direct_sun,
+        }
+    }
+
+    /// Highest level whose lower boundary scaled by `factor` is reached.
+    fn highest_level(&self, lux: Lux, factor: f32) -> LightLevel {
+        LEVELS
+            .iter()
+            .rev()
+            .find(|level| lux >= self.lower_boundary(**level) * factor)
+            .copied()
+            .unwrap_or(LightLevel::Dark)
+    }
+}
+
+/// Light level transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transition {
+    /// Previous light level. `None` for the first classification.
+    pub from: Option<LightLevel>,
+    /// New light level.
+    pub to: LightLevel,
+}
+
+const MAX_HYSTERESIS: f32 = 0.9;
+
+/// Ambient light level classifier with hysteresis
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Classifier {
+    bands: LightLevelBands,
+    hysteresis: f32,
+    level: Option<LightLevel>,
+}
+
+impl Classifier {
+    /// Create a new classifier.
+    ///
+    /// `hysteresis` is relative to each boundary. For example, with a
+    /// hysteresis of 0.1 the level changes from `Indoor` to `Overcast` at
+    /// 1100 lux and back at 900 lux with the default boundaries.
+    ///
+    /// `hysteresis` is clamped to the range `[0.0, 0.9]` since at 1.0 the
+    /// level could never go down again.
+    pub fn new(bands: LightLevelBands, hysteresis: f32) -> Self {
+        let hysteresis = if hysteresis > 0.0 {
+            hysteresis.min(MAX_HYSTERESIS)
+        } else {
+            0.0
+        };
+        Classifier {
+            bands,
+            hysteresis,
+            level: None,
+        }
+    }
+
+    /// Current light level. `None` before the first reading.
+    pub fn level(&self) -> Option<LightLevel> {
+        self.level
+    }
+
+    /// Classify a lux reading.
+    ///
+    /// Returns the transition if the light level changed.
+    pub fn update(&mut self, lux: Lux) -> Option<Transition> {
+        let new_level = match self.level {
+            None => self.bands.highest_level(lux, 1.0),
+            Some(current) => {
+                let up = self.bands.highest_level(lux, 1.0 + self.hysteresis);
+                let down = self.bands.highest_level(lux, 1.0 - self.hysteresis);
+                if up > current {
+                    up
+                } else if down < current {
+                    down
+                } else {
+                    current
+                }
+            }
+        };
+        if self.level == Some(new_level) {
+            return None;
+        }
+        let transition = Transition {
+            from: self.level,
+            to: new_level,
+        };
+        self.level = Some(new_level);
+        Some(transition)
+    }
+
+    /// Read the lux intensity from the device and classify it.
+    pub fn poll<I2C, D, E>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+    ) -> Result<Option<Transition>, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let lux = sensor.read_lux()?;
+        Ok(self.update(lux))
+    }
+
+    /// Forget the current light level.
+    pub fn reset(&mut self) {
+        self.level = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new() -> Classifier {
+        Classifier::new(LightLevelBands::default(), 0.1)
+    }
+
+    fn transition(from: LightLevel, to: LightLevel) -> Option<Transition> {
+        Some(Transition {
+            from: Some(from),
+            to,
+        })
+    }
+
+    #[test]
+    fn first_reading_emits_transition() {
+        let mut classifier = new();
+        assert_eq!(None, classifier.level());
+        assert_eq!(
+            Some(Transition {
+                from: None,
+                to: LightLevel::Indoor
+            }),
+            classifier.update(Lux::new(300.0))
+        );
+        assert_eq!(Some(LightLevel::Indoor), classifier.level());
+    }
+
+    #[test]
+    fn can_classify_without_hysteresis() {
+        let mut classifier = Classifier::new(LightLevelBands::default(), 0.0);
+        let readings = [
+            (0.0, LightLevel::Dark),
+            (9.9, LightLevel::Dark),
+            (10.0, LightLevel::Dim),
+            (100.0, LightLevel::Indoor),
+            (1_000.0, LightLevel::Overcast),
+            (10_000.0, LightLevel::Daylight),
+            (32_000.0, LightLevel::DirectSun),
+            (188_000.0, LightLevel::DirectSun),
+        ];
+        for (lux, level) in readings.iter() {
+            classifier.update(Lux::new(*lux));
+            assert_eq!(Some(*level), classifier.level());
+        }
+    }
+
+    #[test]
+    fn does_not_chatter_at_boundary() {
+        let mut classifier = new();
+        classifier.update(Lux::new(950.0));
+        assert_eq!(None, classifier.update(Lux::new(1_050.0)));
+        assert_eq!(None, classifier.update(Lux::new(920.0)));
+        assert_eq!(
+            transition(LightLevel::Indoor, LightLevel::Overcast),
+            classifier.update(Lux::new(1_100.0))
+        );
+        assert_eq!(None, classifier.update(Lux::new(950.0)));
+        assert_eq!(
+            transition(LightLevel::Overcast, LightLevel::Indoor),
+            classifier.update(Lux::new(899.0))
+        );
+    }
+
+    #[test]
+    fn can_skip_levels() {
+        let mut classifier = new();
+        classifier.update(Lux::new(0.0));
+        assert_eq!(
+            transition(LightLevel::Dark, LightLevel::DirectSun),
+            classifier.update(Lux::new(100_000.0))
+        );
+    }
+
+    #[test]
+    fn can_go_down_with_excessive_hysteresis() {
+        for hysteresis in [1.0, 5.0].iter() {
+            let mut classifier = Classifier::new(LightLevelBands::default(), *hysteresis);
+            classifier.update(Lux::new(300.0));
+            assert_eq!(
+                transition(LightLevel::Indoor, LightLevel::Dark),
+                classifier.update(Lux::new(0.0))
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_invalid_hysteresis() {
+        let mut classifier = Classifier::new(LightLevelBands::default(), f32::NAN);
+        classifier.update(Lux::new(300.0));
+        assert_eq!(
+            transition(LightLevel::Indoor, LightLevel::Overcast),
+            classifier.update(Lux::new(1000.0))
+        );
+    }
+
+    #[test]
+    fn can_reset() {
+        let mut classifier = new();
+        classifier.update(Lux::new(0.0));
+        classifier.reset();
+        assert_eq!(None, classifier.level());
+    }
+}
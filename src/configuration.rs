@@ -1,27 +1,103 @@
 use crate::{
-    BitFlags, ConfigurationMode, CurrentDivisionRatio, Error, IntegrationTime, Max44009,
-    MeasurementMode, Register,
+    BitFlags, ConfigurationMode, CurrentDivisionRatio, Error, IntegrationTime, Lux, Max44009,
+    MeasurementMode, Operation, Register,
 };
-use embedded_hal::blocking::i2c;
+use embedded_hal::blocking::{delay::DelayUs, i2c};
 
-impl<I2C, E> Max44009<I2C>
+impl<I2C, D, E> Max44009<I2C, D>
 where
-    I2C: i2c::Write<Error = E>,
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
 {
+    /// Enable write verification.
+    ///
+    /// Every configuration, threshold and interrupt enable register write
+    /// will be read back and compared. If the values differ,
+    /// `Error::VerificationFailed` is returned and the driver state is not
+    /// updated.
+    pub fn enable_write_verification(&mut self) {
+        self.verify_writes = true;
+    }
+
+    /// Disable write verification (default).
+    pub fn disable_write_verification(&mut self) {
+        self.verify_writes = false;
+    }
+
     /// Enable interrupt.
     ///
     /// The INT pin will be pulled low if the interrupt condition is triggered.
     pub fn enable_interrupt(&mut self) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[Register::INT_ENABLE, 1])
-            .map_err(Error::I2C)
+        self.write_register(Register::INT_ENABLE, 1)?;
+        debug!("Interrupt enabled");
+        self.int_enable = 1;
+        Ok(())
     }
 
     /// Disable interrupt.
     pub fn disable_interrupt(&mut self) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[Register::INT_ENABLE, 0])
-            .map_err(Error::I2C)
+        self.write_register(Register::INT_ENABLE, 0)?;
+        debug!("Interrupt disabled");
+        self.int_enable = 0;
+        Ok(())
+    }
+
+    /// Set the upper threshold in lux.
+    ///
+    /// Accepts `Lux` or, with the `uom` feature, `Illuminance`.
+    ///
+    /// The interrupt is triggered if the lux intensity stays above this
+    /// value for longer than the threshold timer. Only the exponent and the
+    /// 4 most significant bits of the mantissa are compared so the
+    /// effective threshold is rounded up to the next representable value.
+    ///
+    /// The value is converted with the inverse of the calibration correction.
+    pub fn set_upper_threshold(&mut self, lux: impl Into<Lux>) -> Result<(), Error<E>> {
+        let lux = lux.into().value();
+        let threshold = convert_to_threshold(self.calibration.invert(Lux::new(lux)).value())?;
+        self.write_register(Register::UPPER_THRESHOLD_HIGH, threshold)?;
+        debug!(
+            "Upper threshold: {} lx (register value {:#04x})",
+            lux, threshold
+        );
+        self.upper_threshold = threshold;
+        self.upper_threshold_lux = Some(Lux::new(lux));
+        Ok(())
+    }
+
+    /// Set the lower threshold in lux.
+    ///
+    /// Accepts `Lux` or, with the `uom` feature, `Illuminance`.
+    ///
+    /// The interrupt is triggered if the lux intensity stays below this
+    /// value for longer than the threshold timer. Only the exponent and the
+    /// 4 most significant bits of the mantissa are compared so the
+    /// effective threshold is rounded down to the previous representable value.
+    ///
+    /// The value is converted with the inverse of the calibration correction.
+    pub fn set_lower_threshold(&mut self, lux: impl Into<Lux>) -> Result<(), Error<E>> {
+        let lux = lux.into().value();
+        let threshold = convert_to_threshold(self.calibration.invert(Lux::new(lux)).value())?;
+        self.write_register(Register::LOWER_THRESHOLD_HIGH, threshold)?;
+        debug!(
+            "Lower threshold: {} lx (register value {:#04x})",
+            lux, threshold
+        );
+        self.lower_threshold = threshold;
+        self.lower_threshold_lux = Some(Lux::new(lux));
+        Ok(())
+    }
+
+    /// Set the threshold timer in steps of 100ms.
+    ///
+    /// The lux intensity must stay outside of the threshold window for this
+    /// long before the interrupt is triggered. A value of 0 triggers the
+    /// interrupt immediately.
+    pub fn set_threshold_timer(&mut self, timer: u8) -> Result<(), Error<E>> {
+        self.write_register(Register::THRESHOLD_TIMER, timer)?;
+        debug!("Threshold timer: {} ms", u32::from(timer) * 100);
+        self.threshold_timer = timer;
+        Ok(())
     }
 
     /// Set the measurement mode.
@@ -44,7 +120,7 @@ where
 
     /// Set integration time. (Only in manual configuration mode).
     pub fn set_integration_time(&mut self, it: IntegrationTime) -> Result<(), Error<E>> {
-        self.assert_is_in_manual_mode()?;
+        self.assert_is_in_manual_mode(Operation::SetIntegrationTime)?;
         let config = self.config & 0b1111_1000;
         match it {
             IntegrationTime::_800ms => self.write_config(config),
@@ -63,7 +139,7 @@ where
         &mut self,
         cdr: CurrentDivisionRatio,
     ) -> Result<(), Error<E>> {
-        self.assert_is_in_manual_mode()?;
+        self.assert_is_in_manual_mode(Operation::SetCurrentDivisionRatio)?;
         let config = self.config;
         match cdr {
             CurrentDivisionRatio::One => self.write_config(config & !BitFlags::CDR),
@@ -72,17 +148,117 @@ where
     }
 
     fn write_config(&mut self, config: u8) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[Register::CONFIGURATION, config])
-            .map_err(Error::I2C)?;
+        self.write_register(Register::CONFIGURATION, config)?;
+        debug!(
+            "Configuration: continuous: {}, manual: {}, CDR: {}, TIM: {}",
+            (config & BitFlags::CONTINUOUS) != 0,
+            (config & BitFlags::MANUAL) != 0,
+            (config & BitFlags::CDR) != 0,
+            config & BitFlags::TIM
+        );
         self.config = config;
         Ok(())
     }
 
-    fn assert_is_in_manual_mode(&self) -> Result<(), Error<E>> {
+    pub(crate) fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        self.with_retry(|i2c, address| i2c.write(address, &[register, value]))?;
+        trace!("Wrote register {:#04x}: {:#010b}", register, value);
+        if self.verify_writes {
+            let mask = compared_bits(register, value);
+            let read = self.read_register(register)?;
+            if (read & mask) != (value & mask) {
+                warn!(
+                    "Write verification failed for register {:#04x}: wrote {:#04x}, read {:#04x}",
+                    register, value, read
+                );
+                return Err(Error::VerificationFailed {
+                    register,
+                    written: value,
+                    read,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_is_in_manual_mode(&self, operation: Operation) -> Result<(), Error<E>> {
         if (self.config & BitFlags::MANUAL) == 0 {
-            return Err(Error::OperationNotAvailable);
+            return Err(Error::OperationNotAvailable(operation));
         }
         Ok(())
     }
 }
+
+/// Bits of a register that reflect the value written to it.
+///
+/// In automatic configuration mode the integration time and current
+/// division ratio are selected by the device.
+pub(crate) fn compared_bits(register: u8, value: u8) -> u8 {
+    match register {
+        Register::INT_ENABLE => 0b0000_0001,
+        Register::CONFIGURATION if (value & BitFlags::MANUAL) == 0 => {
+            BitFlags::CONTINUOUS | BitFlags::MANUAL
+        }
+        Register::CONFIGURATION => {
+            BitFlags::CONTINUOUS | BitFlags::MANUAL | BitFlags::CDR | BitFlags::TIM
+        }
+        _ => 0xFF,
+    }
+}
+
+pub(crate) fn convert_to_threshold<E>(lux: f32) -> Result<u8, Error<E>> {
+    // Same format as the lux high-byte: exponent and 4 MSBs of the mantissa.
+    let counts = lux / 0.045;
+    if !(0.0..=f32::from(0xFF_u8) * f32::from(1_u16 << 14)).contains(&counts) {
+        return Err(Error::InvalidThreshold);
+    }
+    let counts = counts as u32;
+    let mut exp = 0;
+    while (counts >> exp) > 0xFF {
+        exp += 1;
+    }
+    let mantissa = (counts >> exp) as u8;
+    Ok((exp as u8) << 4 | mantissa >> 4)
+}
+
+/// Lux intensity of a threshold register value.
+pub(crate) fn convert_from_threshold(threshold: u8) -> f32 {
+    let exp = threshold >> 4;
+    let mantissa = u32::from(threshold & 0x0F) << 4;
+    (mantissa << exp) as f32 * 0.045
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threshold(lux: f32) -> u8 {
+        convert_to_threshold::<()>(lux).unwrap()
+    }
+
+    #[test]
+    fn can_convert_to_threshold() {
+        assert_eq!(0, threshold(0.0));
+        assert_eq!(0b0000_0001, threshold(0.72));
+        assert_eq!(0b0000_1111, threshold(11.0));
+        assert_eq!(0b0001_1000, threshold(11.6));
+        assert_eq!(0b1110_1111, threshold(188_000.0));
+    }
+
+    #[test]
+    fn can_convert_from_threshold() {
+        assert_eq!(0.0, convert_from_threshold(0));
+        assert_eq!(0.72, convert_from_threshold(0b0000_0001));
+        assert_eq!(
+            threshold(11.6),
+            threshold(convert_from_threshold(0b0001_1000))
+        );
+    }
+
+    #[test]
+    fn cannot_convert_invalid_threshold() {
+        assert!(convert_to_threshold::<()>(-1.0).is_err());
+        assert!(convert_to_threshold::<()>(190_000.0).is_err());
+        assert!(convert_to_threshold::<()>(f32::NAN).is_err());
+    }
+}
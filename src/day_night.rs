@@ -0,0 +1,231 @@
+//! Day/night detection
+//!
+//! State machine for e.g. street light controllers. Separate dusk and dawn
+//! thresholds provide hysteresis and the state only flips after the lux
+//! intensity has stayed beyond the threshold for a minimum dwell time.
+//!
+//! Optionally, the device threshold timer can be used so that the MCU only
+//! needs to check the device when an interrupt happens.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Day or night
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DayNight {
+    /// Day.
+    Day,
+    /// Night.
+    Night,
+}
+
+/// Day/night detector configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DayNightConfig {
+    /// During the day, night starts when the lux intensity stays below this.
+    pub dusk: Lux,
+    /// During the night, day starts when the lux intensity stays above this.
+    ///
+    /// Must be greater than `dusk`.
+    pub dawn: Lux,
+    /// Time the lux intensity must stay below `dusk` to switch to night.
+    pub dusk_dwell_ms: u32,
+    /// Time the lux intensity must stay above `dawn` to switch to day.
+    pub dawn_dwell_ms: u32,
+}
+
+impl Default for DayNightConfig {
+    /// Dusk at 10 lux, dawn at 30 lux, 60 seconds dwell time.
+    fn default() -> Self {
+        DayNightConfig {
+            dusk: Lux::new(10.0),
+            dawn: Lux::new(30.0),
+            dusk_dwell_ms: 60_000,
+            dawn_dwell_ms: 60_000,
+        }
+    }
+}
+
+/// Maximum lux intensity that can be set as upper threshold.
+const MAX_THRESHOLD: Lux = Lux::new(188_000.0);
+
+/// Maximum duration of the device threshold timer.
+const MAX_HARDWARE_TIMER_MS: u32 = 25_500;
+
+/// Day/night detector
+///
+/// Timestamps are in milliseconds from an arbitrary monotonic clock and may
+/// wrap around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DayNightDetector {
+    config: DayNightConfig,
+    state: DayNight,
+    pending_since: Option<u32>,
+}
+
+impl DayNightDetector {
+    /// Create a new detector in the given initial state.
+    pub fn new(config: DayNightConfig, initial: DayNight) -> Self {
+        DayNightDetector {
+            config,
+            state: initial,
+            pending_since: None,
+        }
+    }
+
+    /// Current state.
+    pub fn state(&self) -> DayNight {
+        self.state
+    }
+
+    /// Process a lux reading taken at `now_ms`.
+    ///
+    /// Returns the new state if it flipped.
+    pub fn update(&mut self, lux: Lux, now_ms: u32) -> Option<DayNight> {
+        if !self.is_beyond_threshold(lux) {
+            self.pending_since = None;
+            return None;
+        }
+        let since = *self.pending_since.get_or_insert(now_ms);
+        if now_ms.wrapping_sub(since) < self.dwell_ms() {
+            return None;
+        }
+        self.state = match self.state {
+            DayNight::Day => DayNight::Night,
+            DayNight::Night => DayNight::Day,
+        };
+        self.pending_since = None;
+        Some(self.state)
+    }
+
+    /// Program the device thresholds and threshold timer for the current
+    /// state and enable the interrupt.
+    ///
+    /// During the day the window is `[dusk, max]` and during the night
+    /// `[0, dawn]`, so that the interrupt only happens when the state may
+    /// flip. The threshold timer is set to the dwell time, up to the maximum
+    /// of 25.5 seconds.
+    pub fn configure_hardware<I2C, D, E>(
+        &self,
+        sensor: &mut Max44009<I2C, D>,
+    ) -> Result<(), Error<E>>
+    where
+        I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let (lower, upper) = match self.state {
+            DayNight::Day => (self.config.dusk, MAX_THRESHOLD),
+            DayNight::Night => (Lux::new(0.0), self.config.dawn),
+        };
+        sensor.set_lower_threshold(lower)?;
+        sensor.set_upper_threshold(upper)?;
+        sensor.set_threshold_timer((self.hardware_timer_ms() / 100) as u8)?;
+        sensor.enable_interrupt()
+    }
+
+    /// Check the device after programming it with `configure_hardware()`.
+    ///
+    /// Reads the lux intensity only if an interrupt happened or a state
+    /// change is pending. The time counted by the device threshold timer is
+    /// taken into account for the dwell time. When the state flips, the
+    /// device is programmed for the new state.
+    ///
+    /// Returns the new state if it flipped.
+    pub fn poll_hardware<I2C, D, E>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        now_ms: u32,
+    ) -> Result<Option<DayNight>, Error<E>>
+    where
+        I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let interrupt = sensor.has_interrupt_happened()?;
+        if !interrupt && self.pending_since.is_none() {
+            return Ok(None);
+        }
+        let lux = sensor.read_lux()?;
+        if interrupt && self.pending_since.is_none() && self.is_beyond_threshold(lux) {
+            self.pending_since = Some(now_ms.wrapping_sub(self.hardware_timer_ms()));
+        }
+        let flipped = self.update(lux, now_ms);
+        if flipped.is_some() {
+            self.configure_hardware(sensor)?;
+        }
+        Ok(flipped)
+    }
+
+    fn is_beyond_threshold(&self, lux: Lux) -> bool {
+        match self.state {
+            DayNight::Day => lux < self.config.dusk,
+            DayNight::Night => lux > self.config.dawn,
+        }
+    }
+
+    fn dwell_ms(&self) -> u32 {
+        match self.state {
+            DayNight::Day => self.config.dusk_dwell_ms,
+            DayNight::Night => self.config.dawn_dwell_ms,
+        }
+    }
+
+    fn hardware_timer_ms(&self) -> u32 {
+        self.dwell_ms().min(MAX_HARDWARE_TIMER_MS) / 100 * 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(initial: DayNight) -> DayNightDetector {
+        DayNightDetector::new(
+            DayNightConfig {
+                dusk: Lux::new(10.0),
+                dawn: Lux::new(30.0),
+                dusk_dwell_ms: 1_000,
+                dawn_dwell_ms: 2_000,
+            },
+            initial,
+        )
+    }
+
+    #[test]
+    fn switches_to_night_after_dwell_time() {
+        let mut detector = new(DayNight::Day);
+        assert_eq!(None, detector.update(Lux::new(5.0), 0));
+        assert_eq!(None, detector.update(Lux::new(5.0), 999));
+        assert_eq!(Some(DayNight::Night), detector.update(Lux::new(5.0), 1_000));
+        assert_eq!(DayNight::Night, detector.state());
+    }
+
+    #[test]
+    fn does_not_switch_if_change_is_not_sustained() {
+        let mut detector = new(DayNight::Day);
+        detector.update(Lux::new(5.0), 0);
+        detector.update(Lux::new(15.0), 500);
+        assert_eq!(None, detector.update(Lux::new(5.0), 1_000));
+        assert_eq!(Some(DayNight::Night), detector.update(Lux::new(5.0), 2_000));
+    }
+
+    #[test]
+    fn uses_separate_dawn_threshold() {
+        let mut detector = new(DayNight::Night);
+        assert_eq!(None, detector.update(Lux::new(20.0), 0));
+        assert_eq!(None, detector.update(Lux::new(20.0), 5_000));
+        assert_eq!(None, detector.update(Lux::new(40.0), 6_000));
+        assert_eq!(Some(DayNight::Day), detector.update(Lux::new(40.0), 8_000));
+    }
+
+    #[test]
+    fn supports_timestamp_wrap_around() {
+        let mut detector = new(DayNight::Day);
+        detector.update(Lux::new(5.0), u32::MAX - 500);
+        assert_eq!(Some(DayNight::Night), detector.update(Lux::new(5.0), 500));
+    }
+}
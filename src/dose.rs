@@ -0,0 +1,253 @@
+//! Light dose accumulation
+//!
+//! Integrates lux readings over time into lux-hours. Readings may be taken
+//! at irregular intervals: each interval is integrated with the trapezoidal
+//! rule. Intervals longer than the configured maximum gap are considered
+//! missing data and are not integrated.
+//!
+//! For plant lighting, the dose can also be reported as a daily light
+//! integral (DLI) estimate in mol/m² using a lux to photosynthetic photon
+//! flux density (PPFD) conversion factor that depends on the light source.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+const MS_PER_HOUR: f64 = 3_600_000.0;
+
+/// Light dose accumulator configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoseConfig {
+    /// Intervals between readings longer than this are not integrated.
+    pub max_gap_ms: u32,
+    /// PPFD in µmol/m²/s per lux used for the daily light integral.
+    ///
+    /// About 0.0185 for sunlight, 0.014 for warm white LEDs and 0.013 for
+    /// cool white fluorescent lamps.
+    pub ppfd_per_lux: f32,
+}
+
+impl Default for DoseConfig {
+    /// Maximum gap of 5 minutes. PPFD conversion factor for sunlight.
+    fn default() -> Self {
+        DoseConfig {
+            max_gap_ms: 300_000,
+            ppfd_per_lux: 0.0185,
+        }
+    }
+}
+
+/// Light dose summary
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoseSummary {
+    /// Integrated light exposure in lux-hours.
+    pub lux_hours: f32,
+    /// Time covered by integrated intervals.
+    pub covered_ms: u64,
+    /// Time between readings that was not integrated because of gaps.
+    pub gap_ms: u64,
+    /// Average lux intensity over the covered time.
+    pub average: Lux,
+    /// Highest lux reading.
+    pub peak: Lux,
+    /// Daily light integral estimate in mol/m².
+    pub daily_light_integral: f32,
+}
+
+/// Light dose accumulator
+///
+/// Timestamps are in milliseconds from an arbitrary monotonic clock and may
+/// wrap around. Readings must be added in chronological order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DoseAccumulator {
+    config: DoseConfig,
+    last: Option<(Lux, u32)>,
+    lux_ms: f64,
+    covered_ms: u64,
+    gap_ms: u64,
+    peak: Lux,
+}
+
+impl DoseAccumulator {
+    /// Create a new accumulator.
+    pub fn new(config: DoseConfig) -> Self {
+        DoseAccumulator {
+            config,
+            last: None,
+            lux_ms: 0.0,
+            covered_ms: 0,
+            gap_ms: 0,
+            peak: Lux::new(0.0),
+        }
+    }
+
+    /// Add a lux reading taken at `timestamp_ms`.
+    pub fn add(&mut self, lux: Lux, timestamp_ms: u32) {
+        if let Some((last_lux, last_timestamp)) = self.last {
+            let interval = timestamp_ms.wrapping_sub(last_timestamp);
+            if interval > self.config.max_gap_ms {
+                self.gap_ms += u64::from(interval);
+            } else {
+                let average = (f64::from(last_lux.value()) + f64::from(lux.value())) / 2.0;
+                self.lux_ms += average * f64::from(interval);
+                self.covered_ms += u64::from(interval);
+            }
+        }
+        if lux > self.peak {
+            self.peak = lux;
+        }
+        self.last = Some((lux, timestamp_ms));
+    }
+
+    /// Read the lux intensity from the device and add it.
+    pub fn poll<I2C, D, E>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        now_ms: u32,
+    ) -> Result<Lux, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        let lux = sensor.read_lux()?;
+        self.add(lux, now_ms);
+        Ok(lux)
+    }
+
+    /// Summary of the light dose accumulated so far.
+    pub fn summary(&self) -> DoseSummary {
+        let lux_hours = self.lux_ms / MS_PER_HOUR;
+        let average = if self.covered_ms == 0 {
+            0.0
+        } else {
+            self.lux_ms / self.covered_ms as f64
+        };
+        // µmol/m²/s integrated over seconds, converted to mol/m².
+        let dli = lux_hours * 3_600.0 * f64::from(self.config.ppfd_per_lux) / 1_000_000.0;
+        DoseSummary {
+            lux_hours: lux_hours as f32,
+            covered_ms: self.covered_ms,
+            gap_ms: self.gap_ms,
+            average: Lux::new(average as f32),
+            peak: self.peak,
+            daily_light_integral: dli as f32,
+        }
+    }
+
+    /// Return the summary and start a new accumulation period. (e.g. a day)
+    ///
+    /// The last reading is kept so that the interval until the next reading
+    /// is counted in the new period.
+    pub fn take_summary(&mut self) -> DoseSummary {
+        let summary = self.summary();
+        self.lux_ms = 0.0;
+        self.covered_ms = 0;
+        self.gap_ms = 0;
+        self.peak = self.last.map(|(lux, _)| lux).unwrap_or(Lux::new(0.0));
+        summary
+    }
+
+    /// Discard all readings and the accumulated dose.
+    pub fn reset(&mut self) {
+        *self = DoseAccumulator::new(self.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: u32 = 3_600_000;
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    fn new() -> DoseAccumulator {
+        DoseAccumulator::new(DoseConfig {
+            max_gap_ms: HOUR_MS,
+            ppfd_per_lux: 0.0185,
+        })
+    }
+
+    #[test]
+    fn single_reading_has_no_dose() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), 0);
+        let summary = acc.summary();
+        assert_eq!(0.0, summary.lux_hours);
+        assert_eq!(Lux::new(0.0), summary.average);
+        assert_eq!(Lux::new(100.0), summary.peak);
+    }
+
+    #[test]
+    fn integrates_irregular_intervals() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), 0);
+        acc.add(Lux::new(100.0), HOUR_MS / 4);
+        acc.add(Lux::new(300.0), HOUR_MS);
+        let summary = acc.summary();
+        assert_near(25.0 + 150.0, summary.lux_hours, 0.01);
+        assert_eq!(u64::from(HOUR_MS), summary.covered_ms);
+        assert_near(175.0, summary.average.value(), 0.01);
+        assert_eq!(Lux::new(300.0), summary.peak);
+    }
+
+    #[test]
+    fn skips_gaps() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), 0);
+        acc.add(Lux::new(100.0), HOUR_MS);
+        acc.add(Lux::new(100.0), 4 * HOUR_MS);
+        acc.add(Lux::new(100.0), 5 * HOUR_MS);
+        let summary = acc.summary();
+        assert_near(200.0, summary.lux_hours, 0.01);
+        assert_eq!(2 * u64::from(HOUR_MS), summary.covered_ms);
+        assert_eq!(3 * u64::from(HOUR_MS), summary.gap_ms);
+    }
+
+    #[test]
+    fn supports_timestamp_wrap_around() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), u32::MAX - HOUR_MS / 2 + 1);
+        acc.add(Lux::new(100.0), HOUR_MS / 2);
+        assert_near(100.0, acc.summary().lux_hours, 0.01);
+    }
+
+    #[test]
+    fn can_calculate_daily_light_integral() {
+        let mut acc = new();
+        // 12 hours at 50000 lux of sunlight
+        for hour in 0..=12 {
+            acc.add(Lux::new(50_000.0), hour * HOUR_MS);
+        }
+        assert_near(39.96, acc.summary().daily_light_integral, 0.01);
+    }
+
+    #[test]
+    fn take_summary_starts_new_period() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), 0);
+        acc.add(Lux::new(200.0), HOUR_MS);
+        assert_near(150.0, acc.take_summary().lux_hours, 0.01);
+        acc.add(Lux::new(200.0), 2 * HOUR_MS);
+        let summary = acc.summary();
+        assert_near(200.0, summary.lux_hours, 0.01);
+        assert_eq!(Lux::new(200.0), summary.peak);
+    }
+
+    #[test]
+    fn can_reset() {
+        let mut acc = new();
+        acc.add(Lux::new(100.0), 0);
+        acc.add(Lux::new(100.0), HOUR_MS);
+        acc.reset();
+        acc.add(Lux::new(100.0), 5 * HOUR_MS);
+        assert_eq!(0.0, acc.summary().lux_hours);
+        assert_eq!(0, acc.summary().gap_ms);
+    }
+}
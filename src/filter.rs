@@ -0,0 +1,289 @@
+//! Lux reading filters
+//!
+//! All filters are allocation-free and keep their history in fixed-size
+//! buffers. Since lux intensity spans several decades, any filter can be
+//! wrapped in `LogDomain` so that it operates on the logarithm of the lux
+//! intensity.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Lux reading filter
+pub trait LuxFilter {
+    /// Add a lux reading and return the filtered value.
+    fn update(&mut self, lux: f32) -> f32;
+
+    /// Discard all previous readings.
+    fn reset(&mut self);
+}
+
+/// Moving average over the last `N` readings.
+///
+/// Until `N` readings are available, the average of the available readings
+/// is returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MovingAverage<const N: usize> {
+    buffer: [f32; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Create a new moving average filter.
+    ///
+    /// Panics if `N` is 0.
+    pub fn new() -> Self {
+        assert!(N > 0, "The window must contain at least one reading.");
+        MovingAverage {
+            buffer: [0.0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LuxFilter for MovingAverage<N> {
+    fn update(&mut self, lux: f32) -> f32 {
+        push(&mut self.buffer, &mut self.len, &mut self.next, lux);
+        self.buffer[..self.len].iter().sum::<f32>() / self.len as f32
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Median of the last `N` readings.
+///
+/// Removes isolated spikes without smoothing out steps. Until `N` readings
+/// are available, the median of the available readings is returned. For an
+/// even number of readings the mean of the two middle values is returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Median<const N: usize> {
+    buffer: [f32; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> Median<N> {
+    /// Create a new median filter.
+    ///
+    /// Panics if `N` is 0.
+    pub fn new() -> Self {
+        assert!(N > 0, "The window must contain at least one reading.");
+        Median {
+            buffer: [0.0; N],
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LuxFilter for Median<N> {
+    fn update(&mut self, lux: f32) -> f32 {
+        push(&mut self.buffer, &mut self.len, &mut self.next, lux);
+        let mut sorted = self.buffer;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+        let middle = self.len / 2;
+        if self.len % 2 == 0 {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        }
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Exponential moving average.
+///
+/// Each reading is weighted with `alpha` and the previous output with
+/// `1 - alpha`. Smaller values of `alpha` produce a smoother output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExponentialMovingAverage {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl ExponentialMovingAverage {
+    /// Create a new exponential moving average filter.
+    ///
+    /// `alpha` is clamped to the range `(0.0, 1.0]`.
+    pub fn new(alpha: f32) -> Self {
+        let alpha = if alpha > 0.0 {
+            alpha.min(1.0)
+        } else {
+            f32::EPSILON
+        };
+        ExponentialMovingAverage { alpha, value: None }
+    }
+}
+
+impl LuxFilter for ExponentialMovingAverage {
+    fn update(&mut self, lux: f32) -> f32 {
+        let value = match self.value {
+            Some(previous) => previous + self.alpha * (lux - previous),
+            None => lux,
+        };
+        self.value = Some(value);
+        value
+    }
+
+    fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Operate a filter on the logarithm of the lux intensity.
+///
+/// Since the lux intensity spans six decades, averaging in the log domain
+/// makes the filter respond the same to relative changes regardless of the
+/// brightness. The readings are offset by the sensor resolution (0.045 lux)
+/// so that readings of 0 lux are supported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LogDomain<F> {
+    filter: F,
+}
+
+impl<F: LuxFilter> LogDomain<F> {
+    /// Wrap a filter to operate in the log domain.
+    pub fn new(filter: F) -> Self {
+        LogDomain { filter }
+    }
+
+    /// Return the wrapped filter.
+    pub fn into_inner(self) -> F {
+        self.filter
+    }
+}
+
+const LOG_OFFSET: f32 = 0.045;
+
+impl<F: LuxFilter> LuxFilter for LogDomain<F> {
+    fn update(&mut self, lux: f32) -> f32 {
+        let filtered = self.filter.update(libm::logf(lux + LOG_OFFSET));
+        libm::expf(filtered) - LOG_OFFSET
+    }
+
+    fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+fn push<const N: usize>(buffer: &mut [f32; N], len: &mut usize, next: &mut usize, value: f32) {
+    buffer[*next] = value;
+    *next = (*next + 1) % N;
+    if *len < N {
+        *len += 1;
+    }
+}
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Read the lux intensity and pass it through a filter.
+    ///
+    /// Returns the filtered value.
+    pub fn read_filtered_lux<F: LuxFilter>(&mut self, filter: &mut F) -> Result<Lux, Error<E>> {
+        let lux = self.read_lux()?;
+        Ok(Lux::new(filter.update(lux.value())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    fn update_all<F: LuxFilter>(filter: &mut F, values: &[f32]) -> f32 {
+        let mut output = 0.0;
+        for value in values {
+            output = filter.update(*value);
+        }
+        output
+    }
+
+    #[test]
+    fn moving_average_uses_available_readings() {
+        let mut filter = MovingAverage::<4>::new();
+        assert_near(10.0, filter.update(10.0), 0.001);
+        assert_near(15.0, filter.update(20.0), 0.001);
+    }
+
+    #[test]
+    fn moving_average_discards_old_readings() {
+        let mut filter = MovingAverage::<2>::new();
+        assert_near(25.0, update_all(&mut filter, &[100.0, 20.0, 30.0]), 0.001);
+    }
+
+    #[test]
+    fn median_removes_spikes() {
+        let mut filter = Median::<3>::new();
+        assert_near(11.0, update_all(&mut filter, &[10.0, 1000.0, 11.0]), 0.001);
+        assert_near(12.0, filter.update(12.0), 0.001);
+    }
+
+    #[test]
+    fn median_of_even_count_is_mean_of_middle_values() {
+        let mut filter = Median::<4>::new();
+        assert_near(15.0, update_all(&mut filter, &[10.0, 20.0]), 0.001);
+    }
+
+    #[test]
+    fn exponential_moving_average_starts_at_first_reading() {
+        let mut filter = ExponentialMovingAverage::new(0.5);
+        assert_near(10.0, filter.update(10.0), 0.001);
+        assert_near(15.0, filter.update(20.0), 0.001);
+        assert_near(17.5, filter.update(20.0), 0.001);
+    }
+
+    #[test]
+    fn can_reset() {
+        let mut filter = ExponentialMovingAverage::new(0.5);
+        filter.update(10.0);
+        filter.reset();
+        assert_near(20.0, filter.update(20.0), 0.001);
+        let mut filter = MovingAverage::<2>::new();
+        filter.update(10.0);
+        filter.reset();
+        assert_near(20.0, filter.update(20.0), 0.001);
+    }
+
+    #[test]
+    fn log_domain_averages_geometrically() {
+        let mut filter = LogDomain::new(MovingAverage::<2>::new());
+        assert_near(100.0, update_all(&mut filter, &[10.0, 1000.0]), 0.5);
+    }
+
+    #[test]
+    fn log_domain_supports_zero() {
+        let mut filter = LogDomain::new(MovingAverage::<2>::new());
+        assert_near(0.0, filter.update(0.0), 0.001);
+    }
+}
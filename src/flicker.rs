@@ -0,0 +1,253 @@
+//! Flicker analysis
+//!
+//! With manual configuration mode, continuous measurement and a short
+//! integration time, the device can sample fast enough to observe light
+//! modulation. A burst of readings is collected into a fixed buffer and
+//! analyzed for the flicker percentage and the dominant frequency.
+//!
+//! The sample rate is limited by the integration time and by the time the
+//! I²C transaction takes. At 6.25ms integration time the Nyquist frequency
+//! is 80Hz, so mains-related flicker at 100Hz or 120Hz appears aliased
+//! at 60Hz or 40Hz respectively.
+
+use crate::{ConfigurationMode, Error, IntegrationTime, Lux, Max44009, MeasurementMode};
+use core::f32::consts::PI;
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Clock cycles of a lux reading transaction: address and register write,
+/// repeated start, address and two data bytes, including acknowledge bits
+/// and start/stop conditions.
+const LUX_READ_CLOCK_CYCLES: u32 = 47;
+
+/// Result of a flicker analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlickerAnalysis {
+    /// Percent flicker: `100 * (max - min) / (max + min)`.
+    pub flicker_percent: f32,
+    /// Frequency with the highest spectral magnitude, excluding the average.
+    ///
+    /// `None` if the light is not modulated. Frequencies above the Nyquist
+    /// frequency appear aliased.
+    pub dominant_frequency_hz: Option<f32>,
+    /// Average lux intensity.
+    pub average: Lux,
+    /// Sample rate used.
+    pub sample_rate_hz: f32,
+    /// Highest frequency that can be observed without aliasing.
+    pub nyquist_hz: f32,
+}
+
+/// Flicker analyzer collecting bursts of `N` readings
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FlickerAnalyzer<const N: usize> {
+    samples: [f32; N],
+    integration_time: IntegrationTime,
+    bus_frequency_hz: u32,
+}
+
+impl<const N: usize> FlickerAnalyzer<N> {
+    /// Create a new analyzer.
+    ///
+    /// `bus_frequency_hz` is the I²C bus clock frequency, used to estimate
+    /// the achievable sample rate.
+    pub fn new(integration_time: IntegrationTime, bus_frequency_hz: u32) -> Self {
+        FlickerAnalyzer {
+            samples: [0.0; N],
+            integration_time,
+            bus_frequency_hz,
+        }
+    }
+
+    /// Time between samples in microseconds.
+    ///
+    /// A new reading is available every integration time but it cannot be
+    /// read faster than the I²C transaction takes.
+    pub fn sample_period_us(&self) -> u32 {
+        integration_time_us(self.integration_time).max(self.transfer_time_us())
+    }
+
+    /// Achievable sample rate.
+    pub fn sample_rate_hz(&self) -> f32 {
+        1_000_000.0 / self.sample_period_us() as f32
+    }
+
+    /// Highest frequency that can be observed without aliasing.
+    pub fn nyquist_hz(&self) -> f32 {
+        self.sample_rate_hz() / 2.0
+    }
+
+    /// Set the device to continuous measurement in manual configuration mode
+    /// with the integration time of this analyzer.
+    pub fn configure_sensor<I2C, D, E>(&self, sensor: &mut Max44009<I2C, D>) -> Result<(), Error<E>>
+    where
+        I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+    {
+        sensor.set_measurement_mode(MeasurementMode::Continuous)?;
+        sensor.set_configuration_mode(ConfigurationMode::Manual)?;
+        sensor.set_integration_time(self.integration_time)
+    }
+
+    /// Collect a burst of `N` readings and analyze it.
+    ///
+    /// The device must have been configured with `configure_sensor()`.
+    pub fn collect<I2C, D, E, DELAY>(
+        &mut self,
+        sensor: &mut Max44009<I2C, D>,
+        delay: &mut DELAY,
+    ) -> Result<FlickerAnalysis, Error<E>>
+    where
+        I2C: i2c::WriteRead<Error = E>,
+        D: DelayUs<u32>,
+        DELAY: DelayUs<u32>,
+    {
+        let wait_us = self.sample_period_us() - self.transfer_time_us();
+        for sample in self.samples.iter_mut() {
+            *sample = sensor.read_lux()?.value();
+            if wait_us != 0 {
+                delay.delay_us(wait_us);
+            }
+        }
+        Ok(self.analyze())
+    }
+
+    /// Analyze the last burst of readings.
+    pub fn analyze(&self) -> FlickerAnalysis {
+        analyze(&self.samples, self.sample_rate_hz())
+    }
+
+    /// Readings of the last burst.
+    pub fn samples(&self) -> &[f32; N] {
+        &self.samples
+    }
+
+    fn transfer_time_us(&self) -> u32 {
+        let clock = self.bus_frequency_hz.max(1);
+        (LUX_READ_CLOCK_CYCLES * 1_000_000).div_ceil(clock)
+    }
+}
+
+/// Analyze lux readings taken at a constant sample rate.
+pub fn analyze(samples: &[f32], sample_rate_hz: f32) -> FlickerAnalysis {
+    let nyquist_hz = sample_rate_hz / 2.0;
+    if samples.is_empty() {
+        return FlickerAnalysis {
+            flicker_percent: 0.0,
+            dominant_frequency_hz: None,
+            average: Lux::new(0.0),
+            sample_rate_hz,
+            nyquist_hz,
+        };
+    }
+    let (min, max) = samples.iter().fold((f32::MAX, f32::MIN), |(min, max), s| {
+        (min.min(*s), max.max(*s))
+    });
+    let average = samples.iter().sum::<f32>() / samples.len() as f32;
+    let flicker_percent = if max + min > 0.0 {
+        100.0 * (max - min) / (max + min)
+    } else {
+        0.0
+    };
+    let dominant_frequency_hz = if max > min {
+        dominant_bin(samples, average).map(|k| k as f32 * sample_rate_hz / samples.len() as f32)
+    } else {
+        None
+    };
+    FlickerAnalysis {
+        flicker_percent,
+        dominant_frequency_hz,
+        average: Lux::new(average),
+        sample_rate_hz,
+        nyquist_hz,
+    }
+}
+
+/// DFT bin with the highest magnitude, excluding the average (bin 0).
+fn dominant_bin(samples: &[f32], average: f32) -> Option<usize> {
+    let n = samples.len();
+    let mut best = None;
+    let mut best_magnitude = 0.0;
+    for k in 1..=n / 2 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, sample) in samples.iter().enumerate() {
+            let angle = 2.0 * PI * ((k * i) % n) as f32 / n as f32;
+            re += (sample - average) * libm::cosf(angle);
+            im -= (sample - average) * libm::sinf(angle);
+        }
+        let magnitude = re * re + im * im;
+        if magnitude > best_magnitude {
+            best_magnitude = magnitude;
+            best = Some(k);
+        }
+    }
+    best
+}
+
+fn integration_time_us(it: IntegrationTime) -> u32 {
+    match it {
+        IntegrationTime::_6_25ms => 6_250,
+        IntegrationTime::_12_5ms => 12_500,
+        IntegrationTime::_25ms => 25_000,
+        IntegrationTime::_50ms => 50_000,
+        IntegrationTime::_100ms => 100_000,
+        IntegrationTime::_200ms => 200_000,
+        IntegrationTime::_400ms => 400_000,
+        IntegrationTime::_800ms => 800_000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    fn sine(frequency_hz: f32, sample_rate_hz: f32, depth: f32) -> [f32; 64] {
+        let mut samples = [0.0; 64];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let t = i as f32 / sample_rate_hz;
+            *sample = 100.0 * (1.0 + depth * libm::sinf(2.0 * PI * frequency_hz * t));
+        }
+        samples
+    }
+
+    #[test]
+    fn sample_rate_is_limited_by_integration_time() {
+        let analyzer = FlickerAnalyzer::<64>::new(IntegrationTime::_6_25ms, 400_000);
+        assert_eq!(6_250, analyzer.sample_period_us());
+        assert_near(160.0, analyzer.sample_rate_hz(), 0.001);
+        assert_near(80.0, analyzer.nyquist_hz(), 0.001);
+    }
+
+    #[test]
+    fn sample_rate_is_limited_by_bus_speed() {
+        let analyzer = FlickerAnalyzer::<64>::new(IntegrationTime::_6_25ms, 5_000);
+        assert_eq!(9_400, analyzer.sample_period_us());
+    }
+
+    #[test]
+    fn can_analyze_modulated_light() {
+        let analysis = analyze(&sine(20.0, 160.0, 0.3), 160.0);
+        assert_near(30.0, analysis.flicker_percent, 0.5);
+        assert_near(20.0, analysis.dominant_frequency_hz.unwrap(), 0.001);
+        assert_near(100.0, analysis.average.value(), 0.5);
+    }
+
+    #[test]
+    fn frequencies_above_nyquist_are_aliased() {
+        let analysis = analyze(&sine(100.0, 160.0, 0.3), 160.0);
+        assert_near(60.0, analysis.dominant_frequency_hz.unwrap(), 0.001);
+    }
+
+    #[test]
+    fn steady_light_has_no_flicker() {
+        let analysis = analyze(&[100.0; 16], 160.0);
+        assert_eq!(0.0, analysis.flicker_percent);
+        assert_eq!(None, analysis.dominant_frequency_hz);
+    }
+}
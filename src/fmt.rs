@@ -0,0 +1,40 @@
+//! Internal logging macros
+//!
+//! Messages are emitted through `defmt` and/or `log` depending on the enabled
+//! features. The format strings must be compatible with both.
+
+/// Trace-level log message.
+macro_rules! trace {
+    ($s:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        defmt::trace!($s $(, $arg)*);
+        #[cfg(feature = "log")]
+        log::trace!($s $(, $arg)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        let _ = ($(&$arg),*);
+    }};
+}
+
+/// Debug-level log message.
+macro_rules! debug {
+    ($s:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        defmt::debug!($s $(, $arg)*);
+        #[cfg(feature = "log")]
+        log::debug!($s $(, $arg)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        let _ = ($(&$arg),*);
+    }};
+}
+
+/// Warning-level log message.
+macro_rules! warn {
+    ($s:literal $(, $arg:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        defmt::warn!($s $(, $arg)*);
+        #[cfg(feature = "log")]
+        log::warn!($s $(, $arg)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        let _ = ($(&$arg),*);
+    }};
+}
@@ -0,0 +1,57 @@
+use crate::{configuration::compared_bits, Error, Max44009, Register};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Detect whether the device has been reset and restore its state if so.
+    ///
+    /// If the device loses power while the MCU does not, its configuration
+    /// silently reverts to the power-on defaults. This reads back the
+    /// interrupt enable, configuration, threshold and threshold timer
+    /// registers and compares them to the state set through this driver.
+    /// Any register that diverges is written again.
+    ///
+    /// Returns `true` if a divergence was detected and the state was restored.
+    ///
+    /// In automatic configuration mode the integration time and current
+    /// division ratio are selected by the device so they are not compared.
+    pub fn restore_if_reset(&mut self) -> Result<bool, Error<E>> {
+        let expected = [
+            (Register::INT_ENABLE, self.int_enable),
+            (Register::CONFIGURATION, self.config),
+            (Register::UPPER_THRESHOLD_HIGH, self.upper_threshold),
+            (Register::LOWER_THRESHOLD_HIGH, self.lower_threshold),
+            (Register::THRESHOLD_TIMER, self.threshold_timer),
+        ];
+        let mut restored = false;
+        for (register, value) in expected.iter() {
+            let current = self.read_register(*register)?;
+            let mask = compared_bits(*register, *value);
+            if (current & mask) != (value & mask) {
+                self.write_register(*register, *value)?;
+                restored = true;
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Read the device state into the driver.
+    ///
+    /// Useful if the device was configured before this driver instance was
+    /// created, for example by another process. The interrupt enable,
+    /// configuration, threshold and threshold timer registers are read and
+    /// taken as the state set through this driver.
+    pub fn read_device_state(&mut self) -> Result<(), Error<E>> {
+        self.int_enable = self.read_register(Register::INT_ENABLE)?;
+        self.config = self.read_register(Register::CONFIGURATION)?;
+        self.upper_threshold = self.read_register(Register::UPPER_THRESHOLD_HIGH)?;
+        self.lower_threshold = self.read_register(Register::LOWER_THRESHOLD_HIGH)?;
+        self.threshold_timer = self.read_register(Register::THRESHOLD_TIMER)?;
+        self.upper_threshold_lux = None;
+        self.lower_threshold_lux = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! Integration with the `uom` crate.
+
+use crate::{Error, Lux, Max44009};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+use uom::si::luminance::candela_per_square_meter;
+
+/// Illuminance as a `uom` quantity.
+///
+/// `uom` does not define a dedicated illuminance quantity. Since the
+/// steradian is dimensionless, the lux (cd·sr/m²) has the same dimension as
+/// the candela per square meter so illuminance is represented as a
+/// `Luminance` quantity whose value in base units is the illuminance in lux.
+pub type Illuminance = uom::si::f32::Luminance;
+
+impl From<Lux> for Illuminance {
+    fn from(lux: Lux) -> Self {
+        Illuminance::new::<candela_per_square_meter>(lux.value())
+    }
+}
+
+impl From<Illuminance> for Lux {
+    fn from(illuminance: Illuminance) -> Self {
+        Lux::new(illuminance.get::<candela_per_square_meter>())
+    }
+}
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Read the illuminance as a `uom` quantity.
+    ///
+    /// Returns `Error::Overrange` if the lux intensity is above the
+    /// measurable range.
+    pub fn read_illuminance(&mut self) -> Result<Illuminance, Error<E>> {
+        self.read_lux().map(Illuminance::from)
+    }
+}
@@ -2,6 +2,10 @@
 //! light sensors (ALS), based on the [`embedded-hal`] traits.
 //!
 //! [`embedded-hal`]: https://github.com/rust-embedded/embedded-hal
+//! [`uom`]: https://crates.io/crates/uom
+//! [`serde`]: https://crates.io/crates/serde
+//! [`defmt`]: https://crates.io/crates/defmt
+//! [`log`]: https://crates.io/crates/log
 //!
 //! This driver allows you to:
 //! - Read lux measurement.
@@ -13,6 +17,31 @@
 //! - Read the current division ratio.
 //! - Enable/disable interrupt generation.
 //! - Check if an interrupt has happened.
+//! - Set the upper and lower lux thresholds and the threshold timer.
+//! - Correct readings and thresholds with a calibration (e.g. for a cover glass).
+//! - Calibrate against two or more reference illuminances.
+//! - Save and load the configuration and calibration to/from persistent storage.
+//! - Detect a device reset and restore the configuration.
+//! - Verify configuration writes.
+//! - Retry failed I²C transactions.
+//! - Recover an I²C bus where the device holds SDA low.
+//! - Classify I²C bus errors (e.g. device not present). Supported for
+//!   `linux-embedded-hal` (`linux` feature) and `embedded-hal` 1.0 error kinds
+//!   (`eh1` feature). Other HALs can provide a classification function.
+//! - Filter lux readings (moving average, median, exponential moving average).
+//! - Convert lux readings to foot-candles, exposure value and irradiance.
+//! - Read illuminance as a [`uom`] quantity. (`uom` feature)
+//! - Serialize configuration and reading types with [`serde`]. (`serde` feature)
+//! - Format all types with [`defmt`] and trace register accesses. (`defmt` feature)
+//! - Log bus transactions with the [`log`] crate. (`log` feature)
+//! - Use the sensor from the command line on Linux. (`cli` feature)
+//! - Share the sensor between processes through a Unix domain socket. (`server` feature, Unix only)
+//! - Classify the ambient light level with hysteresis.
+//! - Detect day and night with dwell times, optionally using the device timer.
+//! - Control a display backlight following the ambient light.
+//! - Accumulate the light dose (lux-hours, daily light integral).
+//! - Detect sudden drops and rises of the lux intensity.
+//! - Estimate the light flicker percentage and frequency.
 //!
 //! ## The devices
 //! The MAX44009 and MAX44007 ambient light sensors feature an I2C digital output
@@ -56,6 +85,7 @@
 //! let address = SlaveAddr::default();
 //! let mut sensor = Max44009::new(dev, address);
 //! let lux = sensor.read_lux().unwrap();
+//! println!("{} ({} fc, EV {})", lux, lux.to_foot_candles(), lux.to_exposure_value());
 //! ```
 //!
 //! ### Provide an alternative address
@@ -84,6 +114,84 @@
 //! }
 //! ```
 //!
+//! ### Set the interrupt thresholds
+//!
+//! The interrupt is triggered when the lux intensity stays outside of the
+//! window defined by the thresholds for longer than the threshold timer.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Lux, Max44009, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! sensor.set_lower_threshold(Lux::new(10.0)).unwrap();
+//! sensor.set_upper_threshold(Lux::new(1000.0)).unwrap();
+//! sensor.set_threshold_timer(5).unwrap(); // 500ms
+//! sensor.enable_interrupt().unwrap();
+//! ```
+//!
+//! ### Compensate a tinted cover glass
+//!
+//! The calibration correction is applied to every reading and to the
+//! thresholds so that both are in actual lux.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Calibration, Lux, Max44009, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! // The glass lets through 40% of the light.
+//! sensor.set_calibration(Calibration::from_transmittance(0.4)).unwrap();
+//! sensor.set_upper_threshold(Lux::new(1000.0)).unwrap();
+//! let lux = sensor.read_lux().unwrap();
+//! println!("{}", lux);
+//! ```
+//!
+//! ### Calibrate against reference illuminances
+//!
+//! ```no_run
+//! use linux_embedded_hal::{Delay, I2cdev};
+//! use max44009::{ Calibration, CalibrationProcedure, Lux, Max44009, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! // Average 10 readings taken 800ms apart for each reference point.
+//! let mut procedure = CalibrationProcedure::new(10, 800_000);
+//! // Place the sensor under a 100 lux reference light
+//! procedure.measure(&mut sensor, &mut Delay, Lux::new(100.0)).unwrap();
+//! // Place the sensor under a 1000 lux reference light
+//! procedure.measure(&mut sensor, &mut Delay, Lux::new(1000.0)).unwrap();
+//! let calibration = procedure.linear().unwrap();
+//! let record = calibration.to_bytes();
+//! // Store the record and apply it later
+//! let calibration = Calibration::from_bytes(&record).unwrap();
+//! sensor.set_calibration(calibration).unwrap();
+//! ```
+//!
+//! ### Save and load the configuration and calibration
+//!
+//! Implement the `Storage` trait for your EEPROM or flash memory.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Calibration, Max44009, SlaveAddr };
+//! use max44009::storage::MemoryStorage;
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut storage = MemoryStorage::new();
+//! sensor.set_calibration(Calibration::from_transmittance(0.4)).unwrap();
+//! sensor.enable_interrupt().unwrap();
+//! sensor.save(&mut storage).unwrap();
+//!
+//! // After a reboot:
+//! let dev = sensor.destroy();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! sensor.load(&mut storage).unwrap();
+//! ```
+//!
 //! ### Set the measurement mode to continuous
 //!
 //! ```no_run
@@ -95,6 +203,248 @@
 //! sensor.set_measurement_mode(MeasurementMode::Continuous).unwrap();
 //! ```
 //!
+//! ### Tell a missing device apart from other bus errors
+//!
+//! With the `linux` feature, errors of the `linux-embedded-hal` I²C bus are
+//! classified from their errno value. For other HALs, implement the
+//! `ClassifyBusError` trait for your error type or pass a function to
+//! `Error::bus_error_kind_with()`.
+//!
+//! ```no_run
+//! # #[cfg(feature = "linux")]
+//! # {
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ BusErrorKind, Max44009, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! if let Err(e) = sensor.read_lux() {
+//!     let kind = e.bus_error_kind();
+//!     if matches!(kind, Some(BusErrorKind::AddressNack | BusErrorKind::UnknownNack)) {
+//!         println!("Device not present");
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! ### Filter lux readings
+//!
+//! Moving average of the last 8 readings in the log domain.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr };
+//! use max44009::filter::{ LogDomain, MovingAverage };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut filter = LogDomain::new(MovingAverage::<8>::new());
+//! loop {
+//!     let lux = sensor.read_filtered_lux(&mut filter).unwrap();
+//!     println!("{}", lux);
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Classify the ambient light level
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr };
+//! use max44009::classification::{ Classifier, LightLevelBands };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut classifier = Classifier::new(LightLevelBands::default(), 0.1);
+//! loop {
+//!     if let Some(transition) = classifier.poll(&mut sensor).unwrap() {
+//!         println!("Light level changed to {:?}", transition.to);
+//!     }
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Detect day and night using the device threshold timer
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr };
+//! use max44009::day_night::{ DayNight, DayNightConfig, DayNightDetector };
+//!
+//! # fn milliseconds() -> u32 { 0 }
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut detector = DayNightDetector::new(DayNightConfig::default(), DayNight::Day);
+//! detector.configure_hardware(&mut sensor).unwrap();
+//! loop {
+//!     // e.g. wait for the INT pin or a periodic wake-up
+//!     if let Some(state) = detector.poll_hardware(&mut sensor, milliseconds()).unwrap() {
+//!         println!("Switched to {:?}", state);
+//!     }
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Control a display backlight
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr };
+//! use max44009::backlight::{ BacklightController, BrightnessCurve };
+//! use max44009::filter::{ LogDomain, MovingAverage };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let filter = LogDomain::new(MovingAverage::<4>::new());
+//! let mut backlight = BacklightController::new(BrightnessCurve::default(), filter, Some(0.5), 1023);
+//! loop {
+//!     let duty = backlight.poll(&mut sensor, 100).unwrap();
+//!     println!("PWM duty: {}", duty);
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Accumulate the daily light dose
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr };
+//! use max44009::dose::{ DoseAccumulator, DoseConfig };
+//!
+//! # fn milliseconds() -> u32 { 0 }
+//! # fn end_of_day() -> bool { true }
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut dose = DoseAccumulator::new(DoseConfig::default());
+//! loop {
+//!     dose.poll(&mut sensor, milliseconds()).unwrap();
+//!     if end_of_day() {
+//!         let summary = dose.take_summary();
+//!         println!("{} lux-hours, DLI: {} mol/m²", summary.lux_hours, summary.daily_light_integral);
+//!     }
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Detect sudden shadows
+//!
+//! Continuous measurement with a short integration time for fast reaction.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ ConfigurationMode, IntegrationTime, Max44009, MeasurementMode, SlaveAddr };
+//! use max44009::change::{ ChangeDetector, ChangeDetectorConfig, ChangeEvent };
+//!
+//! # fn milliseconds() -> u32 { 0 }
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! sensor.set_measurement_mode(MeasurementMode::Continuous).unwrap();
+//! sensor.set_configuration_mode(ConfigurationMode::Manual).unwrap();
+//! sensor.set_integration_time(IntegrationTime::_25ms).unwrap();
+//! let mut detector = ChangeDetector::new(ChangeDetectorConfig::default());
+//! loop {
+//!     if let Some(ChangeEvent::SuddenDrop(_)) = detector.poll(&mut sensor, milliseconds()).unwrap() {
+//!         println!("Shadow detected");
+//!     }
+//!     # break;
+//! }
+//! ```
+//!
+//! ### Analyze light flicker
+//!
+//! ```no_run
+//! use linux_embedded_hal::{Delay, I2cdev};
+//! use max44009::{ IntegrationTime, Max44009, SlaveAddr };
+//! use max44009::flicker::FlickerAnalyzer;
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let mut analyzer = FlickerAnalyzer::<128>::new(IntegrationTime::_6_25ms, 400_000);
+//! analyzer.configure_sensor(&mut sensor).unwrap();
+//! let analysis = analyzer.collect(&mut sensor, &mut Delay).unwrap();
+//! println!(
+//!     "Flicker: {}%, frequency: {:?}Hz (Nyquist: {}Hz)",
+//!     analysis.flicker_percent, analysis.dominant_frequency_hz, analysis.nyquist_hz
+//! );
+//! ```
+//!
+//! ### Use `uom` quantities
+//!
+//! With the `uom` feature enabled, readings can be returned as
+//! `Illuminance` quantities and the threshold setters accept them.
+//!
+//! ```no_run
+//! # #[cfg(feature = "uom")]
+//! # {
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Illuminance, Max44009, SlaveAddr };
+//! use uom::si::luminance::candela_per_square_meter as lux;
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! let illuminance = sensor.read_illuminance().unwrap();
+//! println!("{} lx", illuminance.get::<lux>());
+//! sensor.set_upper_threshold(Illuminance::new::<lux>(1000.0)).unwrap();
+//! # }
+//! ```
+//!
+//! ### Serialize configurations with `serde`
+//!
+//! With the `serde` feature enabled, the configuration and reading types
+//! implement `Serialize` and `Deserialize`.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use max44009::{ IntegrationTime, Lux };
+//!
+//! let json = serde_json::to_string(&IntegrationTime::_100ms).unwrap();
+//! assert_eq!("\"_100ms\"", json);
+//! let lux: Lux = serde_json::from_str("123.5").unwrap();
+//! assert_eq!(Lux::new(123.5), lux);
+//! # }
+//! ```
+//!
+//! ### Debug with `defmt`
+//!
+//! With the `defmt` feature enabled, all public types and `Error` implement
+//! `defmt::Format`. Every register read and write is logged at trace level.
+//! Set the `DEFMT_LOG=max44009=trace` environment variable at build time to
+//! see them.
+//!
+//! ### Log bus transactions with `log`
+//!
+//! With the `log` feature enabled, every register read and write is logged
+//! at trace level, the decoded values and retries at debug level and failed
+//! transactions, overrange readings and failed write verifications as
+//! warnings. Use any `log` implementation,
+//! for example `env_logger` with `RUST_LOG=max44009=trace`.
+//!
+//! ### Share the sensor through a Unix domain socket
+//!
+//! With the `server` feature enabled, a server owns the device and serves
+//! readings and configuration changes to other processes. Requests and
+//! responses are JSON objects, one per line. See the `server` module.
+//!
+//! ```no_run
+//! # #[cfg(all(feature = "server", unix))]
+//! # {
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{server::{Client, Server}, Max44009, SlaveAddr};
+//! use std::{os::unix::net::UnixListener, sync::{atomic::AtomicBool, Arc}};
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let sensor = Max44009::new(dev, SlaveAddr::default());
+//! let listener = UnixListener::bind("/run/max44009.sock").unwrap();
+//! let stop = Arc::new(AtomicBool::new(false));
+//! std::thread::spawn(move || Server::new(sensor).run(listener, stop));
+//!
+//! // In another process:
+//! let mut client = Client::connect("/run/max44009.sock").unwrap();
+//! let lux = client.read_lux().unwrap();
+//! # }
+//! ```
+//!
 //! ### Read the parameters selected in automatic mode
 //!
 //! ```no_run
@@ -124,13 +474,95 @@
 //! sensor.set_integration_time(IntegrationTime::_100ms).unwrap();
 //! ```
 //!
+//! ### Verify configuration writes
+//!
+//! When write verification is enabled, every configuration and threshold
+//! write is read back and compared. A mismatch is reported as
+//! `Error::VerificationFailed`.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Error, Max44009, SlaveAddr, MeasurementMode };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! sensor.enable_write_verification();
+//! match sensor.set_measurement_mode(MeasurementMode::Continuous) {
+//!     Err(Error::VerificationFailed { .. }) => println!("Write was corrupted."),
+//!     Err(e) => println!("Error: {}", e),
+//!     Ok(_) => (),
+//! }
+//! ```
+//!
+//! ### Retry failed I²C transactions
+//!
+//! Up to 3 attempts with 1ms delay between them.
+//!
+//! ```no_run
+//! use linux_embedded_hal::{Delay, I2cdev};
+//! use max44009::{ Max44009, RetryPolicy, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new_with_delay(dev, SlaveAddr::default(), Delay);
+//! sensor.set_retry_policy(RetryPolicy { attempts: 3, delay_us: 1000 });
+//! let lux = sensor.read_lux().unwrap();
+//! ```
+//!
+//! ### Recover a locked-up I²C bus
+//!
+//! If the MCU is reset in the middle of a transaction, the device can keep
+//! SDA low and every transaction will fail. The bus can be recovered by
+//! releasing it, clocking out the device through GPIO pins and creating the
+//! driver again.
+//!
+//! ```no_run
+//! use linux_embedded_hal::{Delay, I2cdev, Pin};
+//! use max44009::{ recover_bus, Error, Max44009, SlaveAddr };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! if let Err(Error::I2C(_)) = sensor.read_lux() {
+//!     drop(sensor.destroy());
+//!     let mut scl = Pin::new(3);
+//!     let mut sda = Pin::new(2);
+//!     recover_bus(&mut scl, &mut sda, &mut Delay).unwrap();
+//!     let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//!     sensor = Max44009::new(dev, SlaveAddr::default());
+//! }
+//! ```
+//!
+//! ### Restore the configuration after a device reset
+//!
+//! If the device loses power while the MCU does not, its registers silently
+//! revert to their power-on values. Calling `restore_if_reset()` periodically
+//! reads back the configuration and threshold registers and writes the
+//! configuration set through the driver again if they diverge.
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use max44009::{ Max44009, SlaveAddr, MeasurementMode };
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut sensor = Max44009::new(dev, SlaveAddr::default());
+//! sensor.set_measurement_mode(MeasurementMode::Continuous).unwrap();
+//! loop {
+//!     if sensor.restore_if_reset().unwrap() {
+//!         println!("Device reset detected. Configuration restored.");
+//!     }
+//!     # break;
+//! }
+//! ```
+//!
 
 #![doc(html_root_url = "https://docs.rs/max44009/0.2.0")]
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 #![no_std]
 
-use embedded_hal::blocking::i2c;
+#[cfg(all(feature = "server", unix))]
+extern crate std;
+
+use embedded_hal::blocking::{delay::DelayUs, i2c};
 
 const DEVICE_BASE_ADDRESS: u8 = 0b100_1010;
 
@@ -141,6 +573,9 @@ impl Register {
     const INT_ENABLE: u8 = 0x01;
     const CONFIGURATION: u8 = 0x02;
     const LUX_HIGH: u8 = 0x03;
+    const UPPER_THRESHOLD_HIGH: u8 = 0x05;
+    const LOWER_THRESHOLD_HIGH: u8 = 0x06;
+    const THRESHOLD_TIMER: u8 = 0x07;
 }
 
 struct BitFlags;
@@ -149,36 +584,107 @@ impl BitFlags {
     const CONTINUOUS: u8 = 0b1000_0000;
     const MANUAL: u8 = 0b0100_0000;
     const CDR: u8 = 0b0000_1000;
+    const TIM: u8 = 0b0000_0111;
 }
 
 /// MAX44009 ambient light sensor driver.
 #[derive(Debug)]
-pub struct Max44009<I2C> {
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Max44009<I2C, D = NoDelay> {
     /// The concrete I²C device implementation.
     i2c: I2C,
     /// The I²C device address.
     address: u8,
     /// Configuration register status.
     config: u8,
+    /// Interrupt enable register status.
+    int_enable: u8,
+    /// Upper threshold high-byte register status.
+    upper_threshold: u8,
+    /// Lower threshold high-byte register status.
+    lower_threshold: u8,
+    /// Upper threshold in lux as set through this driver.
+    upper_threshold_lux: Option<Lux>,
+    /// Lower threshold in lux as set through this driver.
+    lower_threshold_lux: Option<Lux>,
+    /// Threshold timer register status.
+    threshold_timer: u8,
+    /// Whether register writes are read back and compared.
+    verify_writes: bool,
+    /// Retry policy for I²C transactions.
+    retry_policy: RetryPolicy,
+    /// Calibration correction applied to readings and thresholds.
+    calibration: Calibration,
+    /// Delay provider used between retries.
+    delay: D,
 }
 
+#[macro_use]
+mod fmt;
+
+pub mod backlight;
+mod bus_recovery;
+mod calibration;
+pub mod change;
+pub mod classification;
 mod configuration;
+pub mod day_night;
+pub mod dose;
+pub mod filter;
+pub mod flicker;
+mod health;
+#[cfg(feature = "uom")]
+mod illuminance;
 mod reading;
+#[cfg(all(feature = "server", unix))]
+pub mod server;
+pub mod storage;
 mod types;
+pub use crate::bus_recovery::recover_bus;
+pub use crate::calibration::{
+    Calibration, CalibrationProcedure, CALIBRATION_RECORD_SIZE, MAX_CALIBRATION_POINTS,
+};
+#[cfg(feature = "uom")]
+pub use crate::illuminance::Illuminance;
 pub use crate::types::{
-    ConfigurationMode, CurrentDivisionRatio, Error, IntegrationTime, MeasurementMode, SlaveAddr,
+    BusErrorKind, BusRecoveryError, ClassifyBusError, ConfigurationMode, CurrentDivisionRatio,
+    Error, IntegrationTime, Lux, MeasurementMode, NoDelay, Operation, RetryPolicy, SlaveAddr,
 };
 
-impl<I2C, E> Max44009<I2C>
+impl<I2C, E> Max44009<I2C, NoDelay>
 where
     I2C: i2c::Write<Error = E>,
 {
     /// Create new instance of the Max44009 device.
     pub fn new(i2c: I2C, address: SlaveAddr) -> Self {
+        Max44009::new_with_delay(i2c, address, NoDelay)
+    }
+}
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::Write<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Create new instance of the Max44009 device with a delay provider.
+    ///
+    /// The delay is used to wait between attempts of failed I²C
+    /// transactions according to the retry policy.
+    pub fn new_with_delay(i2c: I2C, address: SlaveAddr, delay: D) -> Self {
         Max44009 {
             i2c,
             address: address.addr(DEVICE_BASE_ADDRESS),
             config: 0,
+            int_enable: 0,
+            upper_threshold: 0xFF,
+            lower_threshold: 0,
+            upper_threshold_lux: None,
+            lower_threshold_lux: None,
+            threshold_timer: 0xFF,
+            verify_writes: false,
+            retry_policy: RetryPolicy::default(),
+            calibration: Calibration::default(),
+            delay,
         }
     }
 
@@ -186,4 +692,51 @@ where
     pub fn destroy(self) -> I2C {
         self.i2c
     }
+
+    /// Set the retry policy for I²C transactions.
+    ///
+    /// Failed reads and writes are attempted again up to the configured
+    /// number of attempts. If the last attempt fails, its error is returned.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+}
+
+impl<I2C, D> Max44009<I2C, D>
+where
+    D: DelayUs<u32>,
+{
+    fn with_retry<T, E>(
+        &mut self,
+        mut transaction: impl FnMut(&mut I2C, u8) -> Result<T, E>,
+    ) -> Result<T, Error<E>> {
+        let attempts = self.retry_policy.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match transaction(&mut self.i2c, self.address) {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= attempts => {
+                    warn!(
+                        "I2C transaction failed (attempt {} of {})",
+                        attempt, attempts
+                    );
+                    return Err(Error::I2C(e));
+                }
+                Err(_) => {
+                    warn!(
+                        "I2C transaction failed (attempt {} of {}), retrying",
+                        attempt, attempts
+                    );
+                    attempt += 1;
+                    if self.retry_policy.delay_us != 0 {
+                        debug!(
+                            "Waiting {} us before attempt {}",
+                            self.retry_policy.delay_us, attempt
+                        );
+                        self.delay.delay_us(self.retry_policy.delay_us);
+                    }
+                }
+            }
+        }
+    }
 }
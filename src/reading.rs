@@ -1,61 +1,109 @@
-use crate::{BitFlags, CurrentDivisionRatio, Error, IntegrationTime, Max44009, Register};
-use embedded_hal::blocking::i2c;
+use crate::{BitFlags, CurrentDivisionRatio, Error, IntegrationTime, Lux, Max44009, Register};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
 
-impl<I2C, E> Max44009<I2C>
+impl<I2C, D, E> Max44009<I2C, D>
 where
     I2C: i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
 {
     /// Reads whether an interrupt has happened.
     pub fn has_interrupt_happened(&mut self) -> Result<bool, Error<E>> {
-        let mut data = [0];
-        self.i2c
-            .write_read(self.address, &[Register::INT_STATUS], &mut data)
-            .map_err(Error::I2C)
-            .and(Ok(data[0] != 0))
+        let status = self.read_register(Register::INT_STATUS)?;
+        debug!("Interrupt status: {}", status != 0);
+        Ok(status != 0)
     }
 
     /// Read the lux intensity.
-    pub fn read_lux(&mut self) -> Result<f32, Error<E>> {
+    ///
+    /// The calibration correction is applied to the reading.
+    ///
+    /// Returns `Error::Overrange` if the lux intensity is above the
+    /// measurable range.
+    pub fn read_lux(&mut self) -> Result<Lux, Error<E>> {
+        let lux = self.read_uncalibrated_lux()?;
+        Ok(self.calibration.apply(lux))
+    }
+
+    /// Read the lux intensity as measured by the device, without applying
+    /// the calibration correction.
+    ///
+    /// Returns `Error::Overrange` if the lux intensity is above the
+    /// measurable range.
+    pub fn read_uncalibrated_lux(&mut self) -> Result<Lux, Error<E>> {
         let mut data = [0; 2];
-        self.i2c
-            .write_read(self.address, &[Register::LUX_HIGH], &mut data)
-            .map_err(Error::I2C)
-            .and(Ok(convert_to_lux(data[0], data[1])))
+        self.with_retry(|i2c, address| i2c.write_read(address, &[Register::LUX_HIGH], &mut data))?;
+        trace!(
+            "Read lux registers {:#04x}: {:#010b} {:#010b}",
+            Register::LUX_HIGH,
+            data[0],
+            data[1]
+        );
+        if (data[0] & 0xF0) == OVERRANGE_EXPONENT {
+            warn!("Lux intensity above the measurable range");
+            return Err(Error::Overrange);
+        }
+        let lux = convert_to_lux(data[0], data[1]);
+        debug!(
+            "Lux: {} (exponent {}, mantissa {})",
+            lux,
+            data[0] >> 4,
+            (data[0] & 0x0F) << 4 | (data[1] & 0x0F)
+        );
+        Ok(Lux::new(lux))
     }
 
     /// Read the integration time.
     pub fn read_integration_time(&mut self) -> Result<IntegrationTime, Error<E>> {
-        let mut config = [0];
-        self.i2c
-            .write_read(self.address, &[Register::CONFIGURATION], &mut config)
-            .map_err(Error::I2C)?;
-        match config[0] & 0b0000_0111 {
-            0 => Ok(IntegrationTime::_800ms),
-            1 => Ok(IntegrationTime::_400ms),
-            2 => Ok(IntegrationTime::_200ms),
-            3 => Ok(IntegrationTime::_100ms),
-            4 => Ok(IntegrationTime::_50ms),
-            5 => Ok(IntegrationTime::_25ms),
-            6 => Ok(IntegrationTime::_12_5ms),
-            7 => Ok(IntegrationTime::_6_25ms),
+        let config = self.read_register(Register::CONFIGURATION)?;
+        let it = match config & 0b0000_0111 {
+            0 => IntegrationTime::_800ms,
+            1 => IntegrationTime::_400ms,
+            2 => IntegrationTime::_200ms,
+            3 => IntegrationTime::_100ms,
+            4 => IntegrationTime::_50ms,
+            5 => IntegrationTime::_25ms,
+            6 => IntegrationTime::_12_5ms,
+            7 => IntegrationTime::_6_25ms,
             _ => panic!("Programming error!"),
-        }
+        };
+        debug!("Integration time: {:?}", it);
+        Ok(it)
     }
 
     /// Read the current division ratio.
     pub fn read_current_division_ratio(&mut self) -> Result<CurrentDivisionRatio, Error<E>> {
-        let mut config = [0];
-        self.i2c
-            .write_read(self.address, &[Register::CONFIGURATION], &mut config)
-            .map_err(Error::I2C)?;
-        if (config[0] & BitFlags::CDR) == 0 {
-            Ok(CurrentDivisionRatio::One)
+        let config = self.read_register(Register::CONFIGURATION)?;
+        let cdr = if (config & BitFlags::CDR) == 0 {
+            CurrentDivisionRatio::One
         } else {
-            Ok(CurrentDivisionRatio::OneEighth)
+            CurrentDivisionRatio::OneEighth
+        };
+        debug!("Current division ratio: {:?}", cdr);
+        Ok(cdr)
+    }
+
+    /// Read the contents of all registers (0x00 to 0x07).
+    ///
+    /// Useful for debugging. Reading the interrupt status register clears
+    /// the interrupt.
+    pub fn dump_registers(&mut self) -> Result<[u8; 8], Error<E>> {
+        let mut registers = [0; 8];
+        for (register, value) in registers.iter_mut().enumerate() {
+            *value = self.read_register(register as u8)?;
         }
+        Ok(registers)
+    }
+
+    pub(crate) fn read_register(&mut self, register: u8) -> Result<u8, Error<E>> {
+        let mut data = [0];
+        self.with_retry(|i2c, address| i2c.write_read(address, &[register], &mut data))?;
+        trace!("Read register {:#04x}: {:#010b}", register, data[0]);
+        Ok(data[0])
     }
 }
 
+const OVERRANGE_EXPONENT: u8 = 0b1111_0000;
+
 fn convert_to_lux(msb: u8, lsb: u8) -> f32 {
     let mantissa = (msb & 0x0F) << 4 | (lsb & 0x0F);
     let exp = (msb & 0xF0) >> 4;
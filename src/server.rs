@@ -0,0 +1,474 @@
+//! Unix domain socket server and client
+//!
+//! A server owns the `Max44009` driver and serves readings and configuration
+//! changes to any number of clients. Requests and responses are JSON objects,
+//! one per line:
+//!
+//! ```text
+//! > {"command":"read_lux"}
+//! < {"result":"lux","lux":123.4}
+//! > {"command":"set_integration_time","integration_time":"_100ms"}
+//! < {"result":"error","message":"setting the integration time is only available in manual configuration mode"}
+//! ```
+//!
+//! Only available with the `server` feature on Unix platforms.
+
+use crate::{
+    ConfigurationMode, CurrentDivisionRatio, Error, IntegrationTime, Lux, Max44009, MeasurementMode,
+};
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Debug},
+    format,
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    string::{String, ToString},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+    vec::Vec,
+};
+
+/// How often a running server checks whether it should stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Request sent by a client
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Read the lux intensity.
+    ReadLux,
+    /// Read the integration time.
+    ReadIntegrationTime,
+    /// Read the current division ratio.
+    ReadCurrentDivisionRatio,
+    /// Check whether an interrupt has happened.
+    HasInterruptHappened,
+    /// Set the measurement mode.
+    SetMeasurementMode {
+        /// Measurement mode.
+        mode: MeasurementMode,
+    },
+    /// Set the configuration mode.
+    SetConfigurationMode {
+        /// Configuration mode.
+        mode: ConfigurationMode,
+    },
+    /// Set the integration time.
+    SetIntegrationTime {
+        /// Integration time.
+        integration_time: IntegrationTime,
+    },
+    /// Set the current division ratio.
+    SetCurrentDivisionRatio {
+        /// Current division ratio.
+        cdr: CurrentDivisionRatio,
+    },
+    /// Set the upper threshold.
+    SetUpperThreshold {
+        /// Threshold.
+        lux: Lux,
+    },
+    /// Set the lower threshold.
+    SetLowerThreshold {
+        /// Threshold.
+        lux: Lux,
+    },
+    /// Set the threshold timer in steps of 100ms.
+    SetThresholdTimer {
+        /// Timer.
+        timer: u8,
+    },
+    /// Enable the interrupt.
+    EnableInterrupt,
+    /// Disable the interrupt.
+    DisableInterrupt,
+}
+
+/// Response sent by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    /// The request was carried out.
+    Ok,
+    /// Lux intensity.
+    Lux {
+        /// Lux intensity.
+        lux: Lux,
+    },
+    /// Integration time.
+    IntegrationTime {
+        /// Integration time.
+        integration_time: IntegrationTime,
+    },
+    /// Current division ratio.
+    CurrentDivisionRatio {
+        /// Current division ratio.
+        cdr: CurrentDivisionRatio,
+    },
+    /// Whether an interrupt has happened.
+    Interrupt {
+        /// Whether an interrupt has happened.
+        happened: bool,
+    },
+    /// The request failed.
+    Error {
+        /// Error description.
+        message: String,
+    },
+}
+
+/// Server owning the device
+#[derive(Debug)]
+pub struct Server<I2C, D> {
+    sensor: Max44009<I2C, D>,
+}
+
+impl<I2C, D, E> Server<I2C, D>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+    E: Debug,
+{
+    /// Create a new server for the device.
+    pub fn new(sensor: Max44009<I2C, D>) -> Self {
+        Server { sensor }
+    }
+
+    /// Destroy the server, return the device.
+    pub fn destroy(self) -> Max44009<I2C, D> {
+        self.sensor
+    }
+
+    /// Carry out a request.
+    pub fn handle(&mut self, request: Request) -> Response {
+        let sensor = &mut self.sensor;
+        let result = match request {
+            Request::ReadLux => sensor.read_lux().map(|lux| Response::Lux { lux }),
+            Request::ReadIntegrationTime => sensor
+                .read_integration_time()
+                .map(|integration_time| Response::IntegrationTime { integration_time }),
+            Request::ReadCurrentDivisionRatio => sensor
+                .read_current_division_ratio()
+                .map(|cdr| Response::CurrentDivisionRatio { cdr }),
+            Request::HasInterruptHappened => sensor
+                .has_interrupt_happened()
+                .map(|happened| Response::Interrupt { happened }),
+            Request::SetMeasurementMode { mode } => sensor.set_measurement_mode(mode).map(ok),
+            Request::SetConfigurationMode { mode } => sensor.set_configuration_mode(mode).map(ok),
+            Request::SetIntegrationTime { integration_time } => {
+                sensor.set_integration_time(integration_time).map(ok)
+            }
+            Request::SetCurrentDivisionRatio { cdr } => {
+                sensor.set_current_division_ratio(cdr).map(ok)
+            }
+            Request::SetUpperThreshold { lux } => sensor.set_upper_threshold(lux).map(ok),
+            Request::SetLowerThreshold { lux } => sensor.set_lower_threshold(lux).map(ok),
+            Request::SetThresholdTimer { timer } => sensor.set_threshold_timer(timer).map(ok),
+            Request::EnableInterrupt => sensor.enable_interrupt().map(ok),
+            Request::DisableInterrupt => sensor.disable_interrupt().map(ok),
+        };
+        result.unwrap_or_else(|e| Response::Error {
+            message: error_message(&e),
+        })
+    }
+
+    /// Carry out a request encoded as a JSON line and return the encoded
+    /// response.
+    pub fn handle_line(&mut self, line: &str) -> String {
+        let response = match serde_json::from_str(line) {
+            Ok(request) => self.handle(request),
+            Err(e) => Response::Error {
+                message: format!("invalid request: {}", e),
+            },
+        };
+        encode(&response)
+    }
+
+    /// Serve requests from a connection until it is closed.
+    pub fn serve_connection(&mut self, stream: UnixStream) -> io::Result<()> {
+        serve_connection(stream, None, |line| self.handle_line(line))
+    }
+}
+
+impl<I2C, D, E> Server<I2C, D>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E> + Send + 'static,
+    D: DelayUs<u32> + Send + 'static,
+    E: Debug,
+{
+    /// Accept connections and serve each of them in its own thread until
+    /// `stop` is set.
+    ///
+    /// Requests from different connections are carried out one at a time.
+    /// Errors accepting or setting up a connection are logged and the server
+    /// keeps running. Once stopped, all connections are closed and the server
+    /// is returned.
+    pub fn run(self, listener: UnixListener, stop: Arc<AtomicBool>) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        let server = Arc::new(Mutex::new(self));
+        let mut connections = Vec::new();
+        while !stop.load(Ordering::SeqCst) {
+            connections.retain(|connection: &thread::JoinHandle<_>| !connection.is_finished());
+            let stream = match listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(STOP_POLL_INTERVAL))?;
+                Ok(stream)
+            }) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    if e.kind() != ErrorKind::WouldBlock {
+                        warn!("Accepting a connection failed: {}", e.to_string().as_str());
+                    }
+                    thread::sleep(STOP_POLL_INTERVAL);
+                    continue;
+                }
+            };
+            let server = server.clone();
+            let stop = stop.clone();
+            connections.push(thread::spawn(move || {
+                serve_connection(stream, Some(&stop), |line| match server.lock() {
+                    Ok(mut server) => server.handle_line(line),
+                    Err(_) => encode(&Response::Error {
+                        message: "the server state is poisoned".to_string(),
+                    }),
+                })
+            }));
+        }
+        for connection in connections {
+            // Errors only affect the connection they happened on.
+            let _ = connection.join();
+        }
+        match Arc::try_unwrap(server) {
+            Ok(server) => Ok(server.into_inner().unwrap_or_else(|e| e.into_inner())),
+            Err(_) => unreachable!("All connections have been closed."),
+        }
+    }
+}
+
+fn encode(response: &Response) -> String {
+    serde_json::to_string(response).unwrap_or_default()
+}
+
+fn ok(_: ()) -> Response {
+    Response::Ok
+}
+
+fn error_message<E: Debug>(error: &Error<E>) -> String {
+    match error {
+        Error::I2C(e) => format!("{}: {:?}", error, e),
+        _ => error.to_string(),
+    }
+}
+
+/// Serve requests from a connection until it is closed or `stop` is set.
+///
+/// `stop` is checked whenever reading from the stream times out.
+fn serve_connection(
+    stream: UnixStream,
+    stop: Option<&AtomicBool>,
+    mut handle_line: impl FnMut(&str) -> String,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => (),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                // Any partial line read so far is kept in `line`.
+                if stop.is_some_and(|stop| stop.load(Ordering::SeqCst)) {
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+        let request = String::from_utf8_lossy(&line);
+        if !request.trim().is_empty() {
+            let response = handle_line(request.trim_end());
+            writer.write_all(response.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        line.clear();
+    }
+}
+
+/// Client errors
+#[derive(Debug)]
+pub enum ClientError {
+    /// Socket error.
+    Io(io::Error),
+    /// The response could not be decoded.
+    InvalidResponse(serde_json::Error),
+    /// The server closed the connection.
+    Disconnected,
+    /// The server reported an error.
+    Server(String),
+    /// The server sent a response that does not match the request.
+    UnexpectedResponse(Response),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(_) => write!(f, "socket error"),
+            ClientError::InvalidResponse(_) => write!(f, "invalid response"),
+            ClientError::Disconnected => write!(f, "the server closed the connection"),
+            ClientError::Server(message) => write!(f, "server error: {}", message),
+            ClientError::UnexpectedResponse(response) => {
+                write!(f, "unexpected response: {:?}", response)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(e) => Some(e),
+            ClientError::InvalidResponse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+/// Client connected to a server
+#[derive(Debug)]
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect to the server listening at the socket path.
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self, ClientError> {
+        Client::from_stream(UnixStream::connect(path)?)
+    }
+
+    /// Create a client on a connected stream.
+    pub fn from_stream(stream: UnixStream) -> Result<Self, ClientError> {
+        Ok(Client {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    /// Send a request and wait for the response.
+    ///
+    /// Error responses are returned as `ClientError::Server`.
+    pub fn request(&mut self, request: &Request) -> Result<Response, ClientError> {
+        let mut line = serde_json::to_string(request).map_err(ClientError::InvalidResponse)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        line.clear();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(ClientError::Disconnected);
+        }
+        match serde_json::from_str(&line).map_err(ClientError::InvalidResponse)? {
+            Response::Error { message } => Err(ClientError::Server(message)),
+            response => Ok(response),
+        }
+    }
+
+    /// Read the lux intensity.
+    pub fn read_lux(&mut self) -> Result<Lux, ClientError> {
+        match self.request(&Request::ReadLux)? {
+            Response::Lux { lux } => Ok(lux),
+            response => Err(ClientError::UnexpectedResponse(response)),
+        }
+    }
+
+    /// Read the integration time.
+    pub fn read_integration_time(&mut self) -> Result<IntegrationTime, ClientError> {
+        match self.request(&Request::ReadIntegrationTime)? {
+            Response::IntegrationTime { integration_time } => Ok(integration_time),
+            response => Err(ClientError::UnexpectedResponse(response)),
+        }
+    }
+
+    /// Read the current division ratio.
+    pub fn read_current_division_ratio(&mut self) -> Result<CurrentDivisionRatio, ClientError> {
+        match self.request(&Request::ReadCurrentDivisionRatio)? {
+            Response::CurrentDivisionRatio { cdr } => Ok(cdr),
+            response => Err(ClientError::UnexpectedResponse(response)),
+        }
+    }
+
+    /// Check whether an interrupt has happened.
+    pub fn has_interrupt_happened(&mut self) -> Result<bool, ClientError> {
+        match self.request(&Request::HasInterruptHappened)? {
+            Response::Interrupt { happened } => Ok(happened),
+            response => Err(ClientError::UnexpectedResponse(response)),
+        }
+    }
+
+    /// Set the measurement mode.
+    pub fn set_measurement_mode(&mut self, mode: MeasurementMode) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetMeasurementMode { mode })
+    }
+
+    /// Set the configuration mode.
+    pub fn set_configuration_mode(&mut self, mode: ConfigurationMode) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetConfigurationMode { mode })
+    }
+
+    /// Set the integration time. (Only in manual configuration mode)
+    pub fn set_integration_time(&mut self, it: IntegrationTime) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetIntegrationTime {
+            integration_time: it,
+        })
+    }
+
+    /// Set the current division ratio. (Only in manual configuration mode)
+    pub fn set_current_division_ratio(
+        &mut self,
+        cdr: CurrentDivisionRatio,
+    ) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetCurrentDivisionRatio { cdr })
+    }
+
+    /// Set the upper threshold.
+    pub fn set_upper_threshold(&mut self, lux: Lux) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetUpperThreshold { lux })
+    }
+
+    /// Set the lower threshold.
+    pub fn set_lower_threshold(&mut self, lux: Lux) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetLowerThreshold { lux })
+    }
+
+    /// Set the threshold timer in steps of 100ms.
+    pub fn set_threshold_timer(&mut self, timer: u8) -> Result<(), ClientError> {
+        self.request_ok(&Request::SetThresholdTimer { timer })
+    }
+
+    /// Enable the interrupt.
+    pub fn enable_interrupt(&mut self) -> Result<(), ClientError> {
+        self.request_ok(&Request::EnableInterrupt)
+    }
+
+    /// Disable the interrupt.
+    pub fn disable_interrupt(&mut self) -> Result<(), ClientError> {
+        self.request_ok(&Request::DisableInterrupt)
+    }
+
+    fn request_ok(&mut self, request: &Request) -> Result<(), ClientError> {
+        match self.request(request)? {
+            Response::Ok => Ok(()),
+            response => Err(ClientError::UnexpectedResponse(response)),
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Persistent storage of the driver configuration and calibration
+//!
+//! The configuration, interrupt, threshold and threshold timer register
+//! state, the write verification setting, the retry policy and the
+//! calibration are encoded in a versioned record protected by a CRC-16
+//! checksum. The record can be stored in any medium implementing the
+//! `Storage` trait, for example an EEPROM or a flash page.
+
+use crate::{
+    calibration::{Calibration, CALIBRATION_RECORD_SIZE},
+    Error, Max44009, Register, RetryPolicy,
+};
+use core::fmt;
+use embedded_hal::blocking::{delay::DelayUs, i2c};
+
+/// Size of a settings record in bytes.
+pub const SETTINGS_RECORD_SIZE: usize = 16 + CALIBRATION_RECORD_SIZE;
+
+/// Record identifier, distinguishing a record from erased storage.
+const MAGIC: [u8; 2] = *b"MX";
+
+/// Current record format version.
+const VERSION: u8 = 1;
+
+/// Storage medium holding a single settings record
+pub trait Storage {
+    /// Storage error.
+    type Error;
+
+    /// Read the stored data into the buffer.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Store the data.
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// In-memory storage
+///
+/// Useful for testing and for keeping the settings across a re-creation
+/// of the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MemoryStorage<const N: usize = SETTINGS_RECORD_SIZE> {
+    data: [u8; N],
+}
+
+impl<const N: usize> Default for MemoryStorage<N> {
+    /// Erased storage (all bytes `0xFF`).
+    fn default() -> Self {
+        MemoryStorage { data: [0xFF; N] }
+    }
+}
+
+impl MemoryStorage {
+    /// Create a new erased in-memory storage fitting a settings record.
+    ///
+    /// Use `MemoryStorage::<N>::default()` for other capacities.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> MemoryStorage<N> {
+    /// Stored data.
+    pub fn data(&self) -> &[u8; N] {
+        &self.data
+    }
+
+    /// Mutable access to the stored data.
+    pub fn data_mut(&mut self) -> &mut [u8; N] {
+        &mut self.data
+    }
+}
+
+/// The data does not fit into the in-memory storage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapacityExceeded;
+
+impl fmt::Display for CapacityExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage capacity exceeded")
+    }
+}
+
+impl core::error::Error for CapacityExceeded {}
+
+impl<const N: usize> Storage for MemoryStorage<N> {
+    type Error = CapacityExceeded;
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let data = self.data.get(..buffer.len()).ok_or(CapacityExceeded)?;
+        buffer.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let target = self.data.get_mut(..data.len()).ok_or(CapacityExceeded)?;
+        target.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Errors loading the settings
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LoadError<SE, E> {
+    /// Storage error.
+    Storage(SE),
+    /// Error applying the settings to the device.
+    Device(Error<E>),
+    /// No settings record found. (e.g. erased storage)
+    NotFound,
+    /// The record was stored in an unsupported format version.
+    UnsupportedVersion(u8),
+    /// The record checksum does not match its contents.
+    ChecksumMismatch,
+    /// The record contents are not valid.
+    InvalidRecord,
+}
+
+impl<SE, E> fmt::Display for LoadError<SE, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Storage(_) => write!(f, "storage error"),
+            LoadError::Device(e) => write!(f, "{}", e),
+            LoadError::NotFound => write!(f, "no settings record found"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported settings record version {}", version)
+            }
+            LoadError::ChecksumMismatch => write!(f, "settings record checksum mismatch"),
+            LoadError::InvalidRecord => write!(f, "invalid settings record"),
+        }
+    }
+}
+
+impl<SE, E> core::error::Error for LoadError<SE, E>
+where
+    SE: core::error::Error + 'static,
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            LoadError::Storage(e) => Some(e),
+            LoadError::Device(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Driver settings contained in a record
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    config: u8,
+    int_enable: u8,
+    upper_threshold: u8,
+    lower_threshold: u8,
+    threshold_timer: u8,
+    verify_writes: bool,
+    retry_policy: RetryPolicy,
+    calibration: Calibration,
+}
+
+impl Settings {
+    fn to_bytes(self) -> [u8; SETTINGS_RECORD_SIZE] {
+        let mut bytes = [0; SETTINGS_RECORD_SIZE];
+        bytes[0..2].copy_from_slice(&MAGIC);
+        bytes[2] = VERSION;
+        bytes[3] = self.config;
+        bytes[4] = self.int_enable;
+        bytes[5] = self.upper_threshold;
+        bytes[6] = self.lower_threshold;
+        bytes[7] = self.threshold_timer;
+        bytes[8] = u8::from(self.verify_writes);
+        bytes[9] = self.retry_policy.attempts;
+        bytes[10..14].copy_from_slice(&self.retry_policy.delay_us.to_le_bytes());
+        bytes[14..14 + CALIBRATION_RECORD_SIZE].copy_from_slice(&self.calibration.to_bytes());
+        let crc = crc16(&bytes[..SETTINGS_RECORD_SIZE - 2]);
+        bytes[SETTINGS_RECORD_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes<SE, E>(bytes: &[u8; SETTINGS_RECORD_SIZE]) -> Result<Self, LoadError<SE, E>> {
+        if bytes[0..2] != MAGIC {
+            return Err(LoadError::NotFound);
+        }
+        if bytes[2] != VERSION {
+            return Err(LoadError::UnsupportedVersion(bytes[2]));
+        }
+        let crc = u16::from_le_bytes([
+            bytes[SETTINGS_RECORD_SIZE - 2],
+            bytes[SETTINGS_RECORD_SIZE - 1],
+        ]);
+        if crc != crc16(&bytes[..SETTINGS_RECORD_SIZE - 2]) {
+            return Err(LoadError::ChecksumMismatch);
+        }
+        let calibration = Calibration::from_bytes(&bytes[14..14 + CALIBRATION_RECORD_SIZE])
+            .ok_or(LoadError::InvalidRecord)?;
+        let verify_writes = match bytes[8] {
+            0 => false,
+            1 => true,
+            _ => return Err(LoadError::InvalidRecord),
+        };
+        Ok(Settings {
+            config: bytes[3],
+            int_enable: bytes[4],
+            upper_threshold: bytes[5],
+            lower_threshold: bytes[6],
+            threshold_timer: bytes[7],
+            verify_writes,
+            retry_policy: RetryPolicy {
+                attempts: bytes[9],
+                delay_us: u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]),
+            },
+            calibration,
+        })
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+impl<I2C, D, E> Max44009<I2C, D>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    /// Save the configuration and calibration to the storage.
+    ///
+    /// This includes the state of the configuration, interrupt enable,
+    /// threshold and threshold timer registers as set through this driver,
+    /// the write verification setting, the retry policy and the calibration.
+    pub fn save<S: Storage>(&self, storage: &mut S) -> Result<(), S::Error> {
+        let settings = Settings {
+            config: self.config,
+            int_enable: self.int_enable,
+            upper_threshold: self.upper_threshold,
+            lower_threshold: self.lower_threshold,
+            threshold_timer: self.threshold_timer,
+            verify_writes: self.verify_writes,
+            retry_policy: self.retry_policy,
+            calibration: self.calibration,
+        };
+        storage.write(&settings.to_bytes())
+    }
+
+    /// Load the configuration and calibration from the storage and apply
+    /// them to the driver and the device.
+    ///
+    /// The threshold, threshold timer, configuration and interrupt enable
+    /// registers are written in this order. If the record cannot be read or
+    /// is not valid, the driver state is not changed.
+    ///
+    /// If writing a register fails, the driver state reflects the registers
+    /// written so far. The write verification setting, the retry policy and
+    /// the calibration are only applied once all registers are written.
+    pub fn load<S: Storage>(&mut self, storage: &mut S) -> Result<(), LoadError<S::Error, E>> {
+        let mut bytes = [0; SETTINGS_RECORD_SIZE];
+        storage.read(&mut bytes).map_err(LoadError::Storage)?;
+        let settings = Settings::from_bytes(&bytes)?;
+        self.write_register(Register::UPPER_THRESHOLD_HIGH, settings.upper_threshold)
+            .map_err(LoadError::Device)?;
+        self.upper_threshold = settings.upper_threshold;
+        self.upper_threshold_lux = None;
+        self.write_register(Register::LOWER_THRESHOLD_HIGH, settings.lower_threshold)
+            .map_err(LoadError::Device)?;
+        self.lower_threshold = settings.lower_threshold;
+        self.lower_threshold_lux = None;
+        self.write_register(Register::THRESHOLD_TIMER, settings.threshold_timer)
+            .map_err(LoadError::Device)?;
+        self.threshold_timer = settings.threshold_timer;
+        self.write_register(Register::CONFIGURATION, settings.config)
+            .map_err(LoadError::Device)?;
+        self.config = settings.config;
+        self.write_register(Register::INT_ENABLE, settings.int_enable)
+            .map_err(LoadError::Device)?;
+        self.int_enable = settings.int_enable;
+        self.verify_writes = settings.verify_writes;
+        self.retry_policy = settings.retry_policy;
+        self.calibration = settings.calibration;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lux;
+
+    type Result = core::result::Result<Settings, LoadError<(), ()>>;
+
+    fn settings() -> Settings {
+        Settings {
+            config: 0b1100_0011,
+            int_enable: 1,
+            upper_threshold: 0b0111_1010,
+            lower_threshold: 0b0011_0101,
+            threshold_timer: 5,
+            verify_writes: true,
+            retry_policy: RetryPolicy {
+                attempts: 3,
+                delay_us: 1000,
+            },
+            calibration: Calibration::new(2.5, Lux::new(1.0)),
+        }
+    }
+
+    #[test]
+    fn can_calculate_crc() {
+        assert_eq!(0x29B1, crc16(b"123456789"));
+    }
+
+    #[test]
+    fn can_encode_and_decode() {
+        let result: Result = Settings::from_bytes(&settings().to_bytes());
+        assert_eq!(settings(), result.unwrap());
+    }
+
+    #[test]
+    fn detects_erased_storage() {
+        let result: Result = Settings::from_bytes(&[0xFF; SETTINGS_RECORD_SIZE]);
+        assert!(matches!(result, Err(LoadError::NotFound)));
+    }
+
+    #[test]
+    fn detects_unsupported_version() {
+        let mut bytes = settings().to_bytes();
+        bytes[2] = 2;
+        let result: Result = Settings::from_bytes(&bytes);
+        assert!(matches!(result, Err(LoadError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut bytes = settings().to_bytes();
+        bytes[5] ^= 0x01;
+        let result: Result = Settings::from_bytes(&bytes);
+        assert!(matches!(result, Err(LoadError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn memory_storage_checks_capacity() {
+        let mut storage = MemoryStorage::<4>::default();
+        assert_eq!(Err(CapacityExceeded), storage.write(&[0; 5]));
+        assert_eq!(Ok(()), storage.write(&[1, 2]));
+        assert_eq!(&[1, 2, 0xFF, 0xFF], storage.data());
+    }
+}
@@ -1,17 +1,379 @@
 //! Public types
 
+use core::{fmt, iter, ops};
+use embedded_hal::blocking::delay::DelayUs;
+
 /// All possible errors in this crate
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error<E> {
     /// I²C bus error.
     I2C(E),
-    /// A manual-configuration-mode-only was attempted while in automatic
-    /// configuration mode.
-    OperationNotAvailable,
+    /// A manual-configuration-mode-only operation was attempted while in
+    /// automatic configuration mode.
+    OperationNotAvailable(Operation),
+    /// The provided threshold is outside of the measurable lux range.
+    InvalidThreshold,
+    /// The lux intensity is above the measurable range.
+    Overrange,
+    /// The calibration cannot be applied or inverted.
+    InvalidCalibration,
+    /// The value read back after a register write did not match the value
+    /// written. (Only with write verification enabled)
+    VerificationFailed {
+        /// Register address.
+        register: u8,
+        /// Value written.
+        written: u8,
+        /// Value read back.
+        read: u8,
+    },
+}
+
+impl<E> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::I2C(_) => write!(f, "I²C bus error"),
+            Error::OperationNotAvailable(operation) => write!(
+                f,
+                "{} is only available in manual configuration mode",
+                operation
+            ),
+            Error::InvalidThreshold => write!(f, "threshold outside of the measurable lux range"),
+            Error::Overrange => write!(f, "lux intensity above the measurable range"),
+            Error::InvalidCalibration => write!(f, "invalid calibration"),
+            Error::VerificationFailed {
+                register,
+                written,
+                read,
+            } => write!(
+                f,
+                "write verification failed for register {:#04x}: wrote {:#04x}, read {:#04x}",
+                register, written, read
+            ),
+        }
+    }
+}
+
+impl<E> core::error::Error for Error<E>
+where
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::I2C(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> Error<E> {
+    /// Classify the I²C bus error with a function.
+    ///
+    /// Useful for I²C errors that do not implement `ClassifyBusError`.
+    ///
+    /// Returns `None` if this is not an I²C bus error.
+    pub fn bus_error_kind_with<F>(&self, classify: F) -> Option<BusErrorKind>
+    where
+        F: FnOnce(&E) -> BusErrorKind,
+    {
+        match self {
+            Error::I2C(e) => Some(classify(e)),
+            _ => None,
+        }
+    }
+}
+
+impl<E: ClassifyBusError> Error<E> {
+    /// Classify the I²C bus error.
+    ///
+    /// This allows telling a device that is not present on the bus apart
+    /// from a transient bus error.
+    ///
+    /// Returns `None` if this is not an I²C bus error.
+    pub fn bus_error_kind(&self) -> Option<BusErrorKind> {
+        self.bus_error_kind_with(ClassifyBusError::bus_error_kind)
+    }
+}
+
+/// Classification of I²C bus errors
+///
+/// Implemented for the `linux-embedded-hal` I²C error with the `linux`
+/// feature. For other HALs, implement this for your own error type or use
+/// `Error::bus_error_kind_with()`. With the `eh1` feature, any
+/// `embedded-hal` 1.0 `i2c::ErrorKind` can be converted into a
+/// `BusErrorKind`.
+pub trait ClassifyBusError {
+    /// Classify the error.
+    fn bus_error_kind(&self) -> BusErrorKind;
+}
+
+#[cfg(feature = "linux")]
+impl ClassifyBusError for linux_embedded_hal::i2cdev::linux::LinuxI2CError {
+    /// Classify the error from the errno value as documented in the Linux
+    /// kernel `Documentation/i2c/fault-codes.rst`.
+    fn bus_error_kind(&self) -> BusErrorKind {
+        use linux_embedded_hal::i2cdev::linux::LinuxI2CError;
+        let errno = match self {
+            LinuxI2CError::Nix(e) => Some(*e as i32),
+            LinuxI2CError::Io(e) => e.raw_os_error(),
+        };
+        match errno {
+            Some(libc::ENXIO) => BusErrorKind::AddressNack,
+            Some(libc::EAGAIN) => BusErrorKind::ArbitrationLoss,
+            Some(libc::EREMOTEIO) => BusErrorKind::UnknownNack,
+            _ => BusErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl From<eh1::i2c::ErrorKind> for BusErrorKind {
+    fn from(kind: eh1::i2c::ErrorKind) -> Self {
+        use eh1::i2c::{ErrorKind, NoAcknowledgeSource};
+        match kind {
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => BusErrorKind::AddressNack,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => BusErrorKind::DataNack,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown) => BusErrorKind::UnknownNack,
+            ErrorKind::ArbitrationLoss => BusErrorKind::ArbitrationLoss,
+            _ => BusErrorKind::Other,
+        }
+    }
+}
+
+/// I²C bus error classification
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BusErrorKind {
+    /// The device did not acknowledge its address.
+    ///
+    /// This usually means that the device is not present on the bus.
+    AddressNack,
+    /// The device did not acknowledge a data byte.
+    DataNack,
+    /// The device did not acknowledge, but it is not known whether it was
+    /// the address or a data byte.
+    ///
+    /// Many Linux I²C bus drivers report a missing device like this.
+    UnknownNack,
+    /// The bus arbitration was lost to another master.
+    ArbitrationLoss,
+    /// Any other bus error.
+    Other,
+}
+
+/// Operations only available in manual configuration mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// Setting the integration time.
+    SetIntegrationTime,
+    /// Setting the current division ratio.
+    SetCurrentDivisionRatio,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::SetIntegrationTime => write!(f, "setting the integration time"),
+            Operation::SetCurrentDivisionRatio => {
+                write!(f, "setting the current division ratio")
+            }
+        }
+    }
+}
+
+/// Errors during I²C bus recovery
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusRecoveryError<E> {
+    /// GPIO pin error.
+    Pin(E),
+    /// SDA is still held low after all clock pulses.
+    SdaStuckLow,
+}
+
+impl<E> fmt::Display for BusRecoveryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusRecoveryError::Pin(_) => write!(f, "GPIO pin error"),
+            BusRecoveryError::SdaStuckLow => write!(f, "SDA still held low after clocking"),
+        }
+    }
+}
+
+impl<E> core::error::Error for BusRecoveryError<E>
+where
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            BusRecoveryError::Pin(e) => Some(e),
+            BusRecoveryError::SdaStuckLow => None,
+        }
+    }
+}
+
+/// Illuminance in lux
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lux(f32);
+
+/// Lux per foot-candle.
+const LUX_PER_FOOT_CANDLE: f32 = 10.763_91;
+
+/// Incident-light meter calibration constant for the exposure value.
+const INCIDENT_LIGHT_CALIBRATION: f32 = 250.0;
+
+/// Approximate luminous efficacy of sunlight in lm/W.
+const SUNLIGHT_LUMINOUS_EFFICACY: f32 = 126.7;
+
+impl Lux {
+    /// Create a new illuminance value from lux.
+    pub const fn new(lux: f32) -> Self {
+        Lux(lux)
+    }
+
+    /// Create a new illuminance value from foot-candles.
+    pub fn from_foot_candles(foot_candles: f32) -> Self {
+        Lux(foot_candles * LUX_PER_FOOT_CANDLE)
+    }
+
+    /// Illuminance in lux.
+    pub const fn value(self) -> f32 {
+        self.0
+    }
+
+    /// Illuminance in foot-candles.
+    pub fn to_foot_candles(self) -> f32 {
+        self.0 / LUX_PER_FOOT_CANDLE
+    }
+
+    /// Photographic exposure value at ISO 100 (EV100).
+    ///
+    /// Uses the incident-light meter calibration constant of 250. Returns
+    /// negative infinity for 0 lux.
+    pub fn to_exposure_value(self) -> f32 {
+        libm::log2f(self.0 * 100.0 / INCIDENT_LIGHT_CALIBRATION)
+    }
+
+    /// Approximate irradiance in W/m².
+    ///
+    /// Assumes the spectrum of sunlight (luminous efficacy of about
+    /// 126.7 lm/W). The result is only an estimate for other light sources.
+    pub fn to_irradiance(self) -> f32 {
+        self.0 / SUNLIGHT_LUMINOUS_EFFICACY
+    }
+}
+
+impl From<Lux> for f32 {
+    fn from(lux: Lux) -> Self {
+        lux.0
+    }
+}
+
+impl fmt::Display for Lux {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        f.write_str(" lx")
+    }
+}
+
+impl ops::Add for Lux {
+    type Output = Lux;
+    fn add(self, rhs: Lux) -> Lux {
+        Lux(self.0 + rhs.0)
+    }
+}
+
+impl ops::AddAssign for Lux {
+    fn add_assign(&mut self, rhs: Lux) {
+        self.0 += rhs.0;
+    }
+}
+
+impl ops::Sub for Lux {
+    type Output = Lux;
+    fn sub(self, rhs: Lux) -> Lux {
+        Lux(self.0 - rhs.0)
+    }
+}
+
+impl ops::SubAssign for Lux {
+    fn sub_assign(&mut self, rhs: Lux) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl ops::Mul<f32> for Lux {
+    type Output = Lux;
+    fn mul(self, rhs: f32) -> Lux {
+        Lux(self.0 * rhs)
+    }
+}
+
+impl ops::Div<f32> for Lux {
+    type Output = Lux;
+    fn div(self, rhs: f32) -> Lux {
+        Lux(self.0 / rhs)
+    }
+}
+
+impl ops::Div for Lux {
+    type Output = f32;
+    fn div(self, rhs: Lux) -> f32 {
+        self.0 / rhs.0
+    }
+}
+
+impl iter::Sum for Lux {
+    fn sum<I: Iterator<Item = Lux>>(iter: I) -> Lux {
+        Lux(iter.map(f32::from).sum())
+    }
+}
+
+/// Retry policy for I²C transactions
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    /// Maximum number of attempts for each transaction, including the
+    /// first one. Values of 0 and 1 disable retrying.
+    pub attempts: u8,
+    /// Delay between attempts in microseconds.
+    ///
+    /// Only has an effect if the driver was created with a delay provider.
+    pub delay_us: u32,
+}
+
+impl Default for RetryPolicy {
+    /// No retries
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 1,
+            delay_us: 0,
+        }
+    }
+}
+
+/// Delay provider that does not wait.
+///
+/// Used when the driver is created without a delay provider.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoDelay;
+
+impl DelayUs<u32> for NoDelay {
+    fn delay_us(&mut self, _us: u32) {}
 }
 
 /// Measurement mode
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MeasurementMode {
     /// Once every 800ms mode (default).
     ///
@@ -28,6 +390,8 @@ pub enum MeasurementMode {
 
 /// Configuration mode
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConfigurationMode {
     /// Automatic mode (default).
     ///
@@ -43,6 +407,8 @@ pub enum ConfigurationMode {
 
 /// Integration time
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IntegrationTime {
     /// 6.25ms. (Only in manual mode)
     _6_25ms,
@@ -64,6 +430,8 @@ pub enum IntegrationTime {
 
 /// Current division ratio
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CurrentDivisionRatio {
     /// No current division (default).
     ///
@@ -78,6 +446,8 @@ pub enum CurrentDivisionRatio {
 
 /// Possible slave addresses
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SlaveAddr {
     /// Default slave address
     Default,
@@ -106,6 +476,96 @@ mod tests {
     use super::*;
     use crate::DEVICE_BASE_ADDRESS;
 
+    #[derive(Debug)]
+    struct BusError(BusErrorKind);
+
+    impl ClassifyBusError for BusError {
+        fn bus_error_kind(&self) -> BusErrorKind {
+            self.0
+        }
+    }
+
+    #[test]
+    fn can_classify_bus_errors() {
+        let error = Error::I2C(BusError(BusErrorKind::AddressNack));
+        assert_eq!(Some(BusErrorKind::AddressNack), error.bus_error_kind());
+    }
+
+    #[test]
+    fn can_classify_bus_errors_with_function() {
+        let error = Error::I2C(5);
+        let kind = error.bus_error_kind_with(|e| {
+            if *e == 5 {
+                BusErrorKind::DataNack
+            } else {
+                BusErrorKind::Other
+            }
+        });
+        assert_eq!(Some(BusErrorKind::DataNack), kind);
+    }
+
+    #[test]
+    fn other_errors_are_not_classified() {
+        assert_eq!(None, Error::<BusError>::Overrange.bus_error_kind());
+    }
+
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn can_convert_eh1_error_kinds() {
+        use eh1::i2c::{ErrorKind, NoAcknowledgeSource};
+        let kinds = [
+            (
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+                BusErrorKind::AddressNack,
+            ),
+            (
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+                BusErrorKind::DataNack,
+            ),
+            (
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+                BusErrorKind::UnknownNack,
+            ),
+            (ErrorKind::ArbitrationLoss, BusErrorKind::ArbitrationLoss),
+            (ErrorKind::Bus, BusErrorKind::Other),
+            (ErrorKind::Overrun, BusErrorKind::Other),
+        ];
+        for (kind, expected) in kinds.iter() {
+            assert_eq!(*expected, BusErrorKind::from(*kind));
+        }
+    }
+
+    fn assert_near(a: f32, b: f32, epsilon: f32) {
+        assert!((a - b).abs() < epsilon);
+    }
+
+    #[test]
+    fn can_convert_lux() {
+        assert_near(10.0, Lux::new(107.639).to_foot_candles(), 0.001);
+        assert_near(107.639, Lux::from_foot_candles(10.0).value(), 0.001);
+        assert_near(0.0, Lux::new(2.5).to_exposure_value(), 0.001);
+        assert_near(15.0, Lux::new(81_920.0).to_exposure_value(), 0.001);
+        assert_near(1000.0, Lux::new(126_700.0).to_irradiance(), 0.001);
+    }
+
+    #[test]
+    fn can_operate_on_lux() {
+        assert_eq!(Lux::new(3.0), Lux::new(1.0) + Lux::new(2.0));
+        assert_eq!(Lux::new(1.0), Lux::new(3.0) - Lux::new(2.0));
+        assert_eq!(Lux::new(6.0), Lux::new(3.0) * 2.0);
+        assert_eq!(Lux::new(1.5), Lux::new(3.0) / 2.0);
+        assert_eq!(2.0, Lux::new(4.0) / Lux::new(2.0));
+        let mut lux = Lux::new(1.0);
+        lux += Lux::new(1.0);
+        lux -= Lux::new(0.5);
+        assert_eq!(Lux::new(1.5), lux);
+        assert_eq!(
+            Lux::new(6.0),
+            [1.0, 2.0, 3.0].iter().map(|v| Lux::new(*v)).sum()
+        );
+        assert!(Lux::new(1.0) < Lux::new(2.0));
+    }
+
     #[test]
     fn can_get_default_address() {
         let addr = SlaveAddr::default();
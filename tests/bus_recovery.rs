@@ -0,0 +1,85 @@
+use embedded_hal_mock::eh0::{
+    delay::NoopDelay,
+    digital::{Mock as PinMock, State, Transaction as PinTrans},
+    MockError,
+};
+use max44009::{recover_bus, BusRecoveryError};
+use std::io::ErrorKind;
+
+fn clock_pulses(count: usize) -> Vec<PinTrans> {
+    (0..count)
+        .flat_map(|_| vec![PinTrans::set(State::Low), PinTrans::set(State::High)])
+        .collect()
+}
+
+// Both SCL and SDA are driven low and then released.
+fn stop_condition() -> Vec<PinTrans> {
+    vec![PinTrans::set(State::Low), PinTrans::set(State::High)]
+}
+
+#[test]
+fn only_issues_stop_if_bus_is_free() {
+    let mut scl = PinMock::new(&stop_condition());
+    let mut sda = PinMock::new(
+        &[
+            vec![PinTrans::set(State::High), PinTrans::get(State::High)],
+            stop_condition(),
+        ]
+        .concat(),
+    );
+    recover_bus(&mut scl, &mut sda, &mut NoopDelay).unwrap();
+    scl.done();
+    sda.done();
+}
+
+#[test]
+fn clocks_until_sda_is_released() {
+    let mut scl = PinMock::new(&[clock_pulses(3), stop_condition()].concat());
+    let mut sda = PinMock::new(
+        &[
+            vec![
+                PinTrans::set(State::High),
+                PinTrans::get(State::Low),
+                PinTrans::get(State::Low),
+                PinTrans::get(State::Low),
+                PinTrans::get(State::High),
+            ],
+            stop_condition(),
+        ]
+        .concat(),
+    );
+    recover_bus(&mut scl, &mut sda, &mut NoopDelay).unwrap();
+    scl.done();
+    sda.done();
+}
+
+#[test]
+fn returns_error_if_sda_stays_low() {
+    let mut scl = PinMock::new(&clock_pulses(9));
+    let mut sda = PinMock::new(
+        &[
+            vec![PinTrans::set(State::High)],
+            vec![PinTrans::get(State::Low); 10],
+        ]
+        .concat(),
+    );
+    match recover_bus(&mut scl, &mut sda, &mut NoopDelay) {
+        Err(BusRecoveryError::SdaStuckLow) => (),
+        _ => panic!("Did not return BusRecoveryError::SdaStuckLow."),
+    }
+    scl.done();
+    sda.done();
+}
+
+#[test]
+fn returns_pin_error() {
+    let mut scl = PinMock::new(&[]);
+    let mut sda =
+        PinMock::new(&[PinTrans::set(State::High).with_error(MockError::Io(ErrorKind::Other))]);
+    match recover_bus(&mut scl, &mut sda, &mut NoopDelay) {
+        Err(BusRecoveryError::Pin(MockError::Io(ErrorKind::Other))) => (),
+        _ => panic!("Did not return BusRecoveryError::Pin."),
+    }
+    scl.done();
+    sda.done();
+}
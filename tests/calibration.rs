@@ -0,0 +1,124 @@
+use embedded_hal_mock::eh0::{delay::NoopDelay, i2c::Transaction as I2cTrans, MockError};
+use max44009::{Calibration, CalibrationProcedure, Error, Lux};
+use std::io::ErrorKind;
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn read_lux(msb: u8, lsb: u8) -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![msb, lsb])
+}
+
+#[test]
+fn applies_calibration_to_readings() {
+    let mut dev = new(&[read_lux(0, 10), read_lux(0, 10)]);
+    dev.set_calibration(Calibration::new(2.0, Lux::new(1.0)))
+        .unwrap();
+    let lux = dev.read_lux().unwrap();
+    assert!((lux.value() - 1.9).abs() < 0.001);
+    let lux = dev.read_uncalibrated_lux().unwrap();
+    assert!((lux.value() - 0.45).abs() < 0.001);
+    destroy(dev);
+}
+
+#[test]
+fn applies_inverse_calibration_to_thresholds() {
+    let mut dev = new(&[
+        I2cTrans::write(
+            DEV_BASE_ADDR,
+            vec![Register::UPPER_THRESHOLD_HIGH, 0b0111_1010],
+        ),
+        I2cTrans::write(
+            DEV_BASE_ADDR,
+            vec![Register::LOWER_THRESHOLD_HIGH, 0b0111_1010],
+        ),
+    ]);
+    dev.set_calibration(Calibration::from_transmittance(0.5))
+        .unwrap();
+    dev.set_upper_threshold(Lux::new(2000.0)).unwrap();
+    dev.set_lower_threshold(Lux::new(2000.0)).unwrap();
+    destroy(dev);
+}
+
+fn write(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write(DEV_BASE_ADDR, vec![register, value])
+}
+
+#[test]
+fn converts_thresholds_again_on_calibration_change() {
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0100_1000),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0110_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0011_1000),
+    ]);
+    dev.set_upper_threshold(Lux::new(1000.0)).unwrap();
+    dev.set_lower_threshold(Lux::new(100.0)).unwrap();
+    dev.set_calibration(Calibration::from_transmittance(0.5))
+        .unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn restores_upper_threshold_if_lower_threshold_write_fails() {
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0100_1000),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0110_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0011_1000)
+            .with_error(MockError::Io(ErrorKind::Other)),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+    ]);
+    dev.set_upper_threshold(Lux::new(1000.0)).unwrap();
+    dev.set_lower_threshold(Lux::new(100.0)).unwrap();
+    match dev.set_calibration(Calibration::from_transmittance(0.5)) {
+        Err(Error::I2C(_)) => (),
+        _ => panic!("Did not return Error::I2C."),
+    }
+    assert_eq!(&Calibration::default(), dev.calibration());
+    destroy(dev);
+}
+
+#[test]
+fn keeps_power_on_default_thresholds_on_calibration_change() {
+    let mut dev = new(&[]);
+    dev.set_calibration(Calibration::from_transmittance(0.5))
+        .unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn rejects_calibration_moving_threshold_out_of_range() {
+    let mut dev = new(&[write(Register::UPPER_THRESHOLD_HIGH, 0b1110_1100)]);
+    dev.set_upper_threshold(Lux::new(150_000.0)).unwrap();
+    match dev.set_calibration(Calibration::new(0.5, Lux::new(0.0))) {
+        Err(Error::InvalidThreshold) => (),
+        _ => panic!("Did not return Error::InvalidThreshold."),
+    }
+    assert_eq!(&Calibration::default(), dev.calibration());
+    destroy(dev);
+}
+
+#[test]
+fn rejects_invalid_calibration() {
+    let mut dev = new(&[]);
+    match dev.set_calibration(Calibration::new(-1.0, Lux::new(0.0))) {
+        Err(Error::InvalidCalibration) => (),
+        _ => panic!("Did not return Error::InvalidCalibration."),
+    }
+    assert_eq!(&Calibration::default(), dev.calibration());
+    destroy(dev);
+}
+
+#[test]
+fn procedure_averages_uncalibrated_readings() {
+    let mut dev = new(&[read_lux(0, 10), read_lux(0, 14)]);
+    dev.set_calibration(Calibration::new(2.0, Lux::new(0.0)))
+        .unwrap();
+    let mut procedure = CalibrationProcedure::new(2, 0);
+    let measured = procedure
+        .measure(&mut dev, &mut NoopDelay, Lux::new(1.0))
+        .unwrap();
+    assert!((measured.value() - 0.54).abs() < 0.001);
+    assert_eq!(&[(measured, Lux::new(1.0))], procedure.points());
+    destroy(dev);
+}
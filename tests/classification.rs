@@ -0,0 +1,22 @@
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::classification::{Classifier, LightLevel, LightLevelBands, Transition};
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+#[test]
+fn can_poll() {
+    let mut dev = new(&[I2cTrans::write_read(
+        DEV_BASE_ADDR,
+        vec![Register::LUX_HIGH],
+        vec![0, 1],
+    )]);
+    let mut classifier = Classifier::new(LightLevelBands::default(), 0.1);
+    assert_eq!(
+        Some(Transition {
+            from: None,
+            to: LightLevel::Dark
+        }),
+        classifier.poll(&mut dev).unwrap()
+    );
+    destroy(dev);
+}
@@ -1,4 +1,4 @@
-use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+use embedded_hal_mock::eh0::i2c::{Mock as I2cMock, Transaction as I2cTrans};
 use max44009::{Max44009, SlaveAddr};
 
 pub const DEV_BASE_ADDR: u8 = 0b100_1010;
@@ -11,6 +11,9 @@ impl Register {
     pub const INT_ENABLE: u8 = 0x01;
     pub const CONFIGURATION: u8 = 0x02;
     pub const LUX_HIGH: u8 = 0x03;
+    pub const UPPER_THRESHOLD_HIGH: u8 = 0x05;
+    pub const LOWER_THRESHOLD_HIGH: u8 = 0x06;
+    pub const THRESHOLD_TIMER: u8 = 0x07;
 }
 
 pub fn new(transactions: &[I2cTrans]) -> Max44009<I2cMock> {
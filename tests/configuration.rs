@@ -1,14 +1,14 @@
-use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
 use max44009::{
-    ConfigurationMode as CM, CurrentDivisionRatio as CDR, Error, IntegrationTime as IT,
-    MeasurementMode as MM,
+    ConfigurationMode as CM, CurrentDivisionRatio as CDR, Error, IntegrationTime as IT, Lux,
+    MeasurementMode as MM, Operation,
 };
 mod common;
 use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
 
-fn assert_operation_not_available_error<T, E>(result: Result<T, Error<E>>) {
+fn assert_operation_not_available_error<T, E>(result: Result<T, Error<E>>, operation: Operation) {
     match result {
-        Err(Error::OperationNotAvailable) => (),
+        Err(Error::OperationNotAvailable(op)) if op == operation => (),
         _ => panic!("Did not return Error::OperationNotAvailable."),
     }
 }
@@ -33,6 +33,52 @@ fn can_disable_interrupt() {
     destroy(dev);
 }
 
+macro_rules! set_threshold_test {
+    ($test_name:ident, $method:ident, $register:ident, $lux:expr, $expected:expr) => {
+        #[test]
+        fn $test_name() {
+            let mut dev = new(&[I2cTrans::write(
+                DEV_BASE_ADDR,
+                vec![Register::$register, $expected],
+            )]);
+            dev.$method($lux).unwrap();
+            destroy(dev);
+        }
+    };
+}
+
+set_threshold_test!(
+    can_set_upper_threshold,
+    set_upper_threshold,
+    UPPER_THRESHOLD_HIGH,
+    Lux::new(1000.0),
+    0b0111_1010
+);
+set_threshold_test!(
+    can_set_lower_threshold,
+    set_lower_threshold,
+    LOWER_THRESHOLD_HIGH,
+    Lux::new(0.72),
+    0b0000_0001
+);
+set_threshold_test!(
+    can_set_threshold_timer,
+    set_threshold_timer,
+    THRESHOLD_TIMER,
+    5,
+    5
+);
+
+#[test]
+fn cannot_set_invalid_threshold() {
+    let mut dev = new(&[]);
+    match dev.set_upper_threshold(Lux::new(200_000.0)) {
+        Err(Error::InvalidThreshold) => (),
+        _ => panic!("Did not return Error::InvalidThreshold."),
+    }
+    destroy(dev);
+}
+
 macro_rules! set_mode_test {
     ($test_name:ident, $method:ident, $enum:ident::$variant:ident, $expected:expr) => {
         #[test]
@@ -76,14 +122,20 @@ set_mode_test!(
 #[test]
 fn cannot_set_current_division_ratio_in_automatic_mode() {
     let mut dev = new(&[]);
-    assert_operation_not_available_error(dev.set_current_division_ratio(CDR::One));
+    assert_operation_not_available_error(
+        dev.set_current_division_ratio(CDR::One),
+        Operation::SetCurrentDivisionRatio,
+    );
     destroy(dev);
 }
 
 #[test]
 fn cannot_set_integration_time_in_automatic_mode() {
     let mut dev = new(&[]);
-    assert_operation_not_available_error(dev.set_integration_time(IT::_100ms));
+    assert_operation_not_available_error(
+        dev.set_integration_time(IT::_100ms),
+        Operation::SetIntegrationTime,
+    );
     destroy(dev);
 }
 
@@ -121,3 +173,51 @@ set_param_test!(can_set_it_50ms, set_integration_time, IT::_50ms, 4);
 set_param_test!(can_set_it_25ms, set_integration_time, IT::_25ms, 5);
 set_param_test!(can_set_it_12_5ms, set_integration_time, IT::_12_5ms, 6);
 set_param_test!(can_set_it_6_25ms, set_integration_time, IT::_6_25ms, 7);
+
+#[test]
+fn can_verify_write() {
+    let mut dev = new(&[
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::CONFIGURATION, 0b1000_0000]),
+        I2cTrans::write_read(
+            DEV_BASE_ADDR,
+            vec![Register::CONFIGURATION],
+            vec![0b1000_0011],
+        ),
+    ]);
+    dev.enable_write_verification();
+    dev.set_measurement_mode(MM::Continuous).unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn returns_error_if_verification_fails() {
+    let mut dev = new(&[
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::THRESHOLD_TIMER, 5]),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::THRESHOLD_TIMER], vec![7]),
+    ]);
+    dev.enable_write_verification();
+    match dev.set_threshold_timer(5) {
+        Err(Error::VerificationFailed {
+            register: Register::THRESHOLD_TIMER,
+            written: 5,
+            read: 7,
+        }) => (),
+        _ => panic!("Did not return Error::VerificationFailed."),
+    }
+    destroy(dev);
+}
+
+#[test]
+fn does_not_update_state_if_verification_fails() {
+    let mut dev = new(&[
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::CONFIGURATION, 0b0100_0000]),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::CONFIGURATION], vec![0]),
+    ]);
+    dev.enable_write_verification();
+    assert!(dev.set_configuration_mode(CM::Manual).is_err());
+    assert_operation_not_available_error(
+        dev.set_integration_time(IT::_100ms),
+        Operation::SetIntegrationTime,
+    );
+    destroy(dev);
+}
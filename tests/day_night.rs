@@ -0,0 +1,85 @@
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::{
+    day_night::{DayNight, DayNightConfig, DayNightDetector},
+    Lux,
+};
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn write(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write(DEV_BASE_ADDR, vec![register, value])
+}
+
+fn read(register: u8, data: Vec<u8>) -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![register], data)
+}
+
+fn day_hardware_configuration() -> Vec<I2cTrans> {
+    vec![
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0000_1101),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b1110_1111),
+        write(Register::THRESHOLD_TIMER, 50),
+        write(Register::INT_ENABLE, 1),
+    ]
+}
+
+fn detector(dusk_dwell_ms: u32) -> DayNightDetector {
+    DayNightDetector::new(
+        DayNightConfig {
+            dusk: Lux::new(10.0),
+            dawn: Lux::new(30.0),
+            dusk_dwell_ms,
+            dawn_dwell_ms: 5_000,
+        },
+        DayNight::Day,
+    )
+}
+
+#[test]
+fn can_configure_hardware() {
+    let mut dev = new(&day_hardware_configuration());
+    detector(5_000).configure_hardware(&mut dev).unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn does_not_read_lux_without_interrupt() {
+    let mut dev = new(&[read(Register::INT_STATUS, vec![0])]);
+    assert_eq!(None, detector(5_000).poll_hardware(&mut dev, 0).unwrap());
+    destroy(dev);
+}
+
+#[test]
+fn flips_on_interrupt_and_reconfigures_hardware() {
+    let mut transactions = day_hardware_configuration();
+    transactions.extend(vec![
+        read(Register::INT_STATUS, vec![1]),
+        read(Register::LUX_HIGH, vec![0, 1]),
+        write(Register::LOWER_THRESHOLD_HIGH, 0),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0010_1010),
+        write(Register::THRESHOLD_TIMER, 50),
+        write(Register::INT_ENABLE, 1),
+    ]);
+    let mut dev = new(&transactions);
+    let mut detector = detector(5_000);
+    detector.configure_hardware(&mut dev).unwrap();
+    assert_eq!(
+        Some(DayNight::Night),
+        detector.poll_hardware(&mut dev, 10_000).unwrap()
+    );
+    destroy(dev);
+}
+
+#[test]
+fn keeps_polling_while_dwell_time_exceeds_hardware_timer() {
+    let mut dev = new(&[
+        read(Register::INT_STATUS, vec![1]),
+        read(Register::LUX_HIGH, vec![0, 1]),
+        read(Register::INT_STATUS, vec![0]),
+        read(Register::LUX_HIGH, vec![0, 1]),
+    ]);
+    let mut detector = detector(60_000);
+    assert_eq!(None, detector.poll_hardware(&mut dev, 100_000).unwrap());
+    assert_eq!(None, detector.poll_hardware(&mut dev, 110_000).unwrap());
+    destroy(dev);
+}
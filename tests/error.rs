@@ -0,0 +1,73 @@
+use embedded_hal_mock::eh0::MockError;
+use max44009::{BusRecoveryError, Error, Operation};
+use std::{error::Error as StdError, io::ErrorKind};
+
+#[test]
+fn can_display_errors() {
+    let errors: [(Error<MockError>, &str); 6] = [
+        (Error::I2C(MockError::Io(ErrorKind::Other)), "I²C bus error"),
+        (
+            Error::OperationNotAvailable(Operation::SetIntegrationTime),
+            "setting the integration time is only available in manual configuration mode",
+        ),
+        (
+            Error::InvalidThreshold,
+            "threshold outside of the measurable lux range",
+        ),
+        (Error::Overrange, "lux intensity above the measurable range"),
+        (Error::InvalidCalibration, "invalid calibration"),
+        (
+            Error::VerificationFailed {
+                register: 2,
+                written: 0x40,
+                read: 0,
+            },
+            "write verification failed for register 0x02: wrote 0x40, read 0x00",
+        ),
+    ];
+    for (error, expected) in errors.iter() {
+        assert_eq!(*expected, error.to_string());
+    }
+}
+
+#[test]
+fn i2c_error_is_source() {
+    let error: Error<MockError> = Error::I2C(MockError::Io(ErrorKind::Other));
+    let source = error.source().unwrap();
+    assert_eq!("I/O error: Other", source.to_string());
+    assert!(Error::<MockError>::Overrange.source().is_none());
+}
+
+#[test]
+fn can_convert_to_boxed_error() {
+    let error: Box<dyn StdError> = Box::new(Error::<MockError>::InvalidThreshold);
+    assert_eq!(
+        "threshold outside of the measurable lux range",
+        error.to_string()
+    );
+}
+
+#[test]
+fn pin_error_is_source() {
+    let error = BusRecoveryError::Pin(MockError::Io(ErrorKind::Other));
+    assert_eq!("GPIO pin error", error.to_string());
+    assert!(error.source().is_some());
+    assert!(BusRecoveryError::<MockError>::SdaStuckLow
+        .source()
+        .is_none());
+}
+
+#[cfg(feature = "linux")]
+#[test]
+fn can_classify_linux_errors() {
+    use linux_embedded_hal::i2cdev::linux::LinuxI2CError;
+    use max44009::BusErrorKind;
+    fn classify(errno: i32) -> Option<BusErrorKind> {
+        let error = LinuxI2CError::Io(std::io::Error::from_raw_os_error(errno));
+        Error::I2C(error).bus_error_kind()
+    }
+    assert_eq!(Some(BusErrorKind::AddressNack), classify(libc::ENXIO));
+    assert_eq!(Some(BusErrorKind::ArbitrationLoss), classify(libc::EAGAIN));
+    assert_eq!(Some(BusErrorKind::UnknownNack), classify(libc::EREMOTEIO));
+    assert_eq!(Some(BusErrorKind::Other), classify(libc::EIO));
+}
@@ -0,0 +1,34 @@
+use embedded_hal_mock::eh0::{delay::NoopDelay, i2c::Transaction as I2cTrans};
+use max44009::{flicker::FlickerAnalyzer, IntegrationTime as IT};
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn write(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write(DEV_BASE_ADDR, vec![register, value])
+}
+
+fn read_lux(lsb: u8) -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![0, lsb])
+}
+
+#[test]
+fn can_configure_sensor() {
+    let mut dev = new(&[
+        write(Register::CONFIGURATION, 0b1000_0000),
+        write(Register::CONFIGURATION, 0b1100_0000),
+        write(Register::CONFIGURATION, 0b1100_0111),
+    ]);
+    let analyzer = FlickerAnalyzer::<4>::new(IT::_6_25ms, 100_000);
+    analyzer.configure_sensor(&mut dev).unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn can_collect_burst() {
+    let mut dev = new(&[read_lux(3), read_lux(1), read_lux(3), read_lux(1)]);
+    let mut analyzer = FlickerAnalyzer::<4>::new(IT::_6_25ms, 100_000);
+    let analysis = analyzer.collect(&mut dev, &mut NoopDelay).unwrap();
+    assert!((analysis.flicker_percent - 50.0).abs() < 0.001);
+    assert_eq!(Some(80.0), analysis.dominant_frequency_hz);
+    destroy(dev);
+}
@@ -0,0 +1,90 @@
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::{Lux, MeasurementMode as MM};
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn read(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![register], vec![value])
+}
+
+fn write(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write(DEV_BASE_ADDR, vec![register, value])
+}
+
+#[test]
+fn does_not_restore_if_not_reset() {
+    let mut dev = new(&[
+        read(Register::INT_ENABLE, 0),
+        read(Register::CONFIGURATION, 0b0000_0011),
+        read(Register::UPPER_THRESHOLD_HIGH, 0xFF),
+        read(Register::LOWER_THRESHOLD_HIGH, 0),
+        read(Register::THRESHOLD_TIMER, 0xFF),
+    ]);
+    assert!(!dev.restore_if_reset().unwrap());
+    destroy(dev);
+}
+
+#[test]
+fn restores_configuration_after_reset() {
+    let mut dev = new(&[
+        write(Register::CONFIGURATION, 0b1000_0000),
+        write(Register::INT_ENABLE, 1),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        read(Register::INT_ENABLE, 0),
+        write(Register::INT_ENABLE, 1),
+        read(Register::CONFIGURATION, 0b0000_0011),
+        write(Register::CONFIGURATION, 0b1000_0000),
+        read(Register::UPPER_THRESHOLD_HIGH, 0xFF),
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        read(Register::LOWER_THRESHOLD_HIGH, 0),
+        read(Register::THRESHOLD_TIMER, 0xFF),
+    ]);
+    dev.set_measurement_mode(MM::Continuous).unwrap();
+    dev.enable_interrupt().unwrap();
+    dev.set_upper_threshold(Lux::new(1000.0)).unwrap();
+    assert!(dev.restore_if_reset().unwrap());
+    destroy(dev);
+}
+
+#[test]
+fn compares_manual_configuration_bits() {
+    let mut dev = new(&[
+        write(Register::CONFIGURATION, 0b0100_0000),
+        write(Register::CONFIGURATION, 0b0100_0111),
+        read(Register::INT_ENABLE, 0),
+        read(Register::CONFIGURATION, 0b0100_0011),
+        write(Register::CONFIGURATION, 0b0100_0111),
+        read(Register::UPPER_THRESHOLD_HIGH, 0xFF),
+        read(Register::LOWER_THRESHOLD_HIGH, 0),
+        read(Register::THRESHOLD_TIMER, 0xFF),
+    ]);
+    dev.set_configuration_mode(max44009::ConfigurationMode::Manual)
+        .unwrap();
+    dev.set_integration_time(max44009::IntegrationTime::_6_25ms)
+        .unwrap();
+    assert!(dev.restore_if_reset().unwrap());
+    destroy(dev);
+}
+
+#[test]
+fn can_read_device_state() {
+    let mut dev = new(&[
+        read(Register::INT_ENABLE, 1),
+        read(Register::CONFIGURATION, 0b0100_0011),
+        read(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        read(Register::LOWER_THRESHOLD_HIGH, 0b0011_0101),
+        read(Register::THRESHOLD_TIMER, 5),
+        read(Register::INT_ENABLE, 1),
+        read(Register::CONFIGURATION, 0b0100_0011),
+        read(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        read(Register::LOWER_THRESHOLD_HIGH, 0b0011_0101),
+        read(Register::THRESHOLD_TIMER, 5),
+        write(Register::CONFIGURATION, 0b0100_0111),
+    ]);
+    dev.read_device_state().unwrap();
+    assert!(!dev.restore_if_reset().unwrap());
+    // Manual configuration mode was read from the device.
+    dev.set_integration_time(max44009::IntegrationTime::_6_25ms)
+        .unwrap();
+    destroy(dev);
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "uom")]
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::{Illuminance, Lux};
+use uom::si::luminance::candela_per_square_meter as lux;
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+#[test]
+fn can_read_illuminance() {
+    let mut dev = new(&[I2cTrans::write_read(
+        DEV_BASE_ADDR,
+        vec![Register::LUX_HIGH],
+        vec![0, 1],
+    )]);
+    let illuminance = dev.read_illuminance().unwrap();
+    assert!((illuminance.get::<lux>() - 0.045).abs() < 0.001);
+    destroy(dev);
+}
+
+#[test]
+fn can_set_threshold_from_illuminance() {
+    let mut dev = new(&[I2cTrans::write(
+        DEV_BASE_ADDR,
+        vec![Register::UPPER_THRESHOLD_HIGH, 0b0111_1010],
+    )]);
+    dev.set_upper_threshold(Illuminance::new::<lux>(1000.0))
+        .unwrap();
+    destroy(dev);
+}
+
+#[test]
+fn can_convert_between_lux_and_illuminance() {
+    let illuminance: Illuminance = Lux::new(250.0).into();
+    assert_eq!(250.0, illuminance.get::<lux>());
+    assert_eq!(Lux::new(250.0), Lux::from(illuminance));
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "log")]
+use embedded_hal_mock::eh0::{i2c::Transaction as I2cTrans, MockError};
+use log::{Level, Log, Metadata, Record};
+use max44009::RetryPolicy;
+use std::{io::ErrorKind, sync::Mutex};
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+struct Logger(Mutex<Vec<(Level, String)>>);
+
+impl Log for Logger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let message = (record.level(), record.args().to_string());
+        self.0.lock().unwrap().push(message);
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger(Mutex::new(Vec::new()));
+
+fn take_messages() -> Vec<(Level, String)> {
+    std::mem::take(&mut *LOGGER.0.lock().unwrap())
+}
+
+// A single test because the logger is global.
+#[test]
+fn logs_transactions() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let mut dev = new(&[I2cTrans::write_read(
+        DEV_BASE_ADDR,
+        vec![Register::LUX_HIGH],
+        vec![0b0000_0001, 0],
+    )]);
+    dev.read_lux().unwrap();
+    destroy(dev);
+    assert_eq!(
+        vec![
+            (
+                Level::Trace,
+                "Read lux registers 0x03: 0b00000001 0b00000000".to_string()
+            ),
+            (
+                Level::Debug,
+                "Lux: 0.72 (exponent 0, mantissa 16)".to_string()
+            ),
+        ],
+        take_messages()
+    );
+
+    let mut dev = new(&[I2cTrans::write_read(
+        DEV_BASE_ADDR,
+        vec![Register::LUX_HIGH],
+        vec![0b1111_0000, 0],
+    )]);
+    assert!(dev.read_lux().is_err());
+    destroy(dev);
+    let messages = take_messages();
+    assert_eq!(
+        (
+            Level::Warn,
+            "Lux intensity above the measurable range".to_string()
+        ),
+        messages[1]
+    );
+
+    let mut dev = new(&[
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1]),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::INT_ENABLE], vec![0]),
+    ]);
+    dev.enable_write_verification();
+    assert!(dev.enable_interrupt().is_err());
+    destroy(dev);
+    let messages = take_messages();
+    assert_eq!(
+        (
+            Level::Warn,
+            "Write verification failed for register 0x01: wrote 0x01, read 0x00".to_string()
+        ),
+        messages[2]
+    );
+
+    let mut dev = new(&[
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1])
+            .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1]),
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 0])
+            .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 0])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ]);
+    dev.set_retry_policy(RetryPolicy {
+        attempts: 2,
+        delay_us: 100,
+    });
+    dev.enable_interrupt().unwrap();
+    assert!(dev.disable_interrupt().is_err());
+    destroy(dev);
+    let messages: Vec<_> = take_messages()
+        .into_iter()
+        .filter(|(level, _)| *level != Level::Trace)
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Level::Warn,
+                "I2C transaction failed (attempt 1 of 2), retrying".to_string()
+            ),
+            (Level::Debug, "Waiting 100 us before attempt 2".to_string()),
+            (Level::Debug, "Interrupt enabled".to_string()),
+            (
+                Level::Warn,
+                "I2C transaction failed (attempt 1 of 2), retrying".to_string()
+            ),
+            (Level::Debug, "Waiting 100 us before attempt 2".to_string()),
+            (
+                Level::Warn,
+                "I2C transaction failed (attempt 2 of 2)".to_string()
+            ),
+        ],
+        messages
+    );
+}
@@ -1,5 +1,5 @@
-use embedded_hal_mock::i2c::Transaction as I2cTrans;
-use max44009::{CurrentDivisionRatio as CDR, IntegrationTime as IT};
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::{filter::MovingAverage, CurrentDivisionRatio as CDR, Error, IntegrationTime as IT};
 mod common;
 use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
 
@@ -35,7 +35,34 @@ fn can_read_lux() {
         vec![0, 1],
     )]);
     let lux = dev.read_lux().unwrap();
-    assert!((lux - 0.045).abs() < 0.001);
+    assert!((lux.value() - 0.045).abs() < 0.001);
+    destroy(dev);
+}
+
+#[test]
+fn can_read_filtered_lux() {
+    let mut dev = new(&[
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![0, 1]),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![0, 3]),
+    ]);
+    let mut filter = MovingAverage::<2>::new();
+    dev.read_filtered_lux(&mut filter).unwrap();
+    let lux = dev.read_filtered_lux(&mut filter).unwrap();
+    assert!((lux.value() - 0.09).abs() < 0.001);
+    destroy(dev);
+}
+
+#[test]
+fn returns_error_on_overrange() {
+    let mut dev = new(&[I2cTrans::write_read(
+        DEV_BASE_ADDR,
+        vec![Register::LUX_HIGH],
+        vec![0b1111_0000, 0],
+    )]);
+    match dev.read_lux() {
+        Err(Error::Overrange) => (),
+        _ => panic!("Did not return Error::Overrange."),
+    }
     destroy(dev);
 }
 
@@ -71,3 +98,18 @@ read_param_test!(can_read_it_50ms, read_integration_time, 4, IT::_50ms);
 read_param_test!(can_read_it_25ms, read_integration_time, 5, IT::_25ms);
 read_param_test!(can_read_it_12_5ms, read_integration_time, 6, IT::_12_5ms);
 read_param_test!(can_read_it_6_25ms, read_integration_time, 7, IT::_6_25ms);
+
+#[test]
+fn can_dump_registers() {
+    let values = [0, 1, 0x43, 0x12, 0x03, 0xFF, 0, 0xFF];
+    let transactions: Vec<_> = values
+        .iter()
+        .enumerate()
+        .map(|(register, value)| {
+            I2cTrans::write_read(DEV_BASE_ADDR, vec![register as u8], vec![*value])
+        })
+        .collect();
+    let mut dev = new(&transactions);
+    assert_eq!(values, dev.dump_registers().unwrap());
+    destroy(dev);
+}
@@ -0,0 +1,99 @@
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal_mock::eh0::{
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use max44009::{Error, Max44009, RetryPolicy, SlaveAddr};
+use std::{cell::Cell, io::ErrorKind, rc::Rc};
+mod common;
+use crate::common::{Register, DEV_BASE_ADDR};
+
+#[derive(Default)]
+struct DelayCounter(Rc<Cell<u32>>);
+
+impl DelayUs<u32> for DelayCounter {
+    fn delay_us(&mut self, us: u32) {
+        self.0.set(self.0.get() + us);
+    }
+}
+
+fn failed_lux_read() -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![0, 0])
+        .with_error(MockError::Io(ErrorKind::Other))
+}
+
+fn new(transactions: &[I2cTrans], attempts: u8) -> Max44009<I2cMock, DelayCounter> {
+    let mut dev = Max44009::new_with_delay(
+        I2cMock::new(transactions),
+        SlaveAddr::default(),
+        DelayCounter::default(),
+    );
+    dev.set_retry_policy(RetryPolicy {
+        attempts,
+        delay_us: 100,
+    });
+    dev
+}
+
+#[test]
+fn does_not_retry_by_default() {
+    let mut dev = common::new(&[failed_lux_read()]);
+    assert!(dev.read_lux().is_err());
+    common::destroy(dev);
+}
+
+#[test]
+fn can_retry_read() {
+    let mut dev = new(
+        &[
+            failed_lux_read(),
+            failed_lux_read(),
+            I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![0, 1]),
+        ],
+        3,
+    );
+    let lux = dev.read_lux().unwrap();
+    assert!((lux.value() - 0.045).abs() < 0.001);
+    dev.destroy().done();
+}
+
+#[test]
+fn can_retry_write() {
+    let mut dev = new(
+        &[
+            I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1])
+                .with_error(MockError::Io(ErrorKind::Other)),
+            I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1]),
+        ],
+        2,
+    );
+    dev.enable_interrupt().unwrap();
+    dev.destroy().done();
+}
+
+#[test]
+fn returns_last_error_when_attempts_exhausted() {
+    let mut dev = new(&[failed_lux_read(), failed_lux_read()], 2);
+    match dev.read_lux() {
+        Err(Error::I2C(MockError::Io(ErrorKind::Other))) => (),
+        _ => panic!("Did not return Error::I2C."),
+    }
+    dev.destroy().done();
+}
+
+#[test]
+fn waits_between_attempts() {
+    let total_us = Rc::new(Cell::new(0));
+    let mut dev = Max44009::new_with_delay(
+        I2cMock::new(&[failed_lux_read(), failed_lux_read(), failed_lux_read()]),
+        SlaveAddr::default(),
+        DelayCounter(total_us.clone()),
+    );
+    dev.set_retry_policy(RetryPolicy {
+        attempts: 3,
+        delay_us: 250,
+    });
+    assert!(dev.read_lux().is_err());
+    assert_eq!(500, total_us.get());
+    dev.destroy().done();
+}
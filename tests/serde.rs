@@ -0,0 +1,99 @@
+#![cfg(feature = "serde")]
+use max44009::{
+    backlight::BrightnessCurve,
+    change::{ChangeDetectorConfig, ChangeEvent},
+    classification::{LightLevel, LightLevelBands, Transition},
+    day_night::{DayNight, DayNightConfig},
+    dose::DoseConfig,
+    BusErrorKind, Calibration, ConfigurationMode, CurrentDivisionRatio, IntegrationTime, Lux,
+    MeasurementMode, Operation, RetryPolicy, SlaveAddr,
+};
+
+macro_rules! round_trip_test {
+    ($name:ident, $value:expr) => {
+        #[test]
+        fn $name() {
+            let value = $value;
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(value, serde_json::from_str(&json).unwrap());
+        }
+    };
+}
+
+round_trip_test!(measurement_mode, MeasurementMode::Continuous);
+round_trip_test!(configuration_mode, ConfigurationMode::Manual);
+round_trip_test!(integration_time, IntegrationTime::_12_5ms);
+round_trip_test!(current_division_ratio, CurrentDivisionRatio::OneEighth);
+round_trip_test!(slave_addr_default, SlaveAddr::Default);
+round_trip_test!(slave_addr_alternative, SlaveAddr::Alternative(true));
+round_trip_test!(lux, Lux::new(123.5));
+round_trip_test!(operation, Operation::SetIntegrationTime);
+round_trip_test!(bus_error_kind, BusErrorKind::AddressNack);
+round_trip_test!(
+    retry_policy,
+    RetryPolicy {
+        attempts: 3,
+        delay_us: 100
+    }
+);
+round_trip_test!(
+    calibration,
+    Calibration::new(2.0, Lux::new(1.0)).with_table(&[
+        (Lux::new(10.0), Lux::new(20.0)),
+        (Lux::new(100.0), Lux::new(150.0)),
+    ])
+);
+round_trip_test!(light_level, LightLevel::Overcast);
+round_trip_test!(light_level_bands, LightLevelBands::default());
+round_trip_test!(
+    transition,
+    Transition {
+        from: Some(LightLevel::Dim),
+        to: LightLevel::Indoor
+    }
+);
+round_trip_test!(day_night, DayNight::Night);
+round_trip_test!(day_night_config, DayNightConfig::default());
+round_trip_test!(brightness_curve, BrightnessCurve::default());
+round_trip_test!(dose_config, DoseConfig::default());
+round_trip_test!(change_detector_config, ChangeDetectorConfig::default());
+round_trip_test!(change_event, ChangeEvent::SuddenDrop(3.5));
+
+#[test]
+fn lux_is_serialized_as_number() {
+    assert_eq!("123.5", serde_json::to_string(&Lux::new(123.5)).unwrap());
+}
+
+#[test]
+fn rejects_invalid_calibration() {
+    let mut json = serde_json::to_value(Calibration::default()).unwrap();
+    json["gain"] = serde_json::json!(-1.0);
+    assert!(serde_json::from_value::<Calibration>(json).is_err());
+    let mut json = serde_json::to_value(Calibration::default()).unwrap();
+    let points: Vec<_> = (1..=9).map(|i| (i as f32, i as f32)).collect();
+    json["table"] = serde_json::to_value(&points[..8]).unwrap();
+    assert!(serde_json::from_value::<Calibration>(json.clone()).is_ok());
+    json["table"] = serde_json::to_value(&points).unwrap();
+    assert!(serde_json::from_value::<Calibration>(json).is_err());
+}
+
+#[test]
+fn serializes_only_used_calibration_points() {
+    let calibration = Calibration::new(2.0, Lux::new(1.0)).with_table(&[
+        (Lux::new(10.0), Lux::new(20.0)),
+        (Lux::new(100.0), Lux::new(150.0)),
+    ]);
+    assert_eq!(
+        serde_json::json!({
+            "gain": 2.0,
+            "offset": 1.0,
+            "table": [[10.0, 20.0], [100.0, 150.0]]
+        }),
+        serde_json::to_value(calibration).unwrap()
+    );
+    let json = serde_json::json!({ "gain": 2.0, "offset": 1.0 });
+    assert_eq!(
+        Calibration::new(2.0, Lux::new(1.0)),
+        serde_json::from_value(json).unwrap()
+    );
+}
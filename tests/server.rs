@@ -0,0 +1,139 @@
+#![cfg(all(feature = "server", unix))]
+use embedded_hal_mock::eh0::i2c::Transaction as I2cTrans;
+use max44009::{
+    server::{Client, ClientError, Request, Response, Server},
+    IntegrationTime, Lux,
+};
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn read_lux(msb: u8, lsb: u8) -> I2cTrans {
+    I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::LUX_HIGH], vec![msb, lsb])
+}
+
+#[test]
+fn can_handle_request() {
+    let mut server = Server::new(new(&[read_lux(0b0000_0001, 0)]));
+    assert_eq!(
+        Response::Lux {
+            lux: Lux::new(0.72)
+        },
+        server.handle(Request::ReadLux)
+    );
+    destroy(server.destroy());
+}
+
+#[test]
+fn can_handle_line() {
+    let transactions = [I2cTrans::write(
+        DEV_BASE_ADDR,
+        vec![Register::UPPER_THRESHOLD_HIGH, 0b0000_1111],
+    )];
+    let mut server = Server::new(new(&transactions));
+    assert_eq!(
+        r#"{"result":"ok"}"#,
+        server.handle_line(r#"{"command":"set_upper_threshold","lux":11.0}"#)
+    );
+    destroy(server.destroy());
+}
+
+#[test]
+fn reports_device_errors() {
+    let mut server = Server::new(new(&[]));
+    let response = server.handle(Request::SetIntegrationTime {
+        integration_time: IntegrationTime::_100ms,
+    });
+    assert!(matches!(response, Response::Error { .. }));
+    destroy(server.destroy());
+}
+
+#[test]
+fn reports_invalid_requests() {
+    let mut server = Server::new(new(&[]));
+    let response: Response = serde_json::from_str(&server.handle_line("{\"command\":")).unwrap();
+    assert!(matches!(response, Response::Error { .. }));
+    let response: Response =
+        serde_json::from_str(&server.handle_line(r#"{"command":"self_destruct"}"#)).unwrap();
+    assert!(matches!(response, Response::Error { .. }));
+    destroy(server.destroy());
+}
+
+#[test]
+fn client_can_talk_to_server() {
+    let transactions = [
+        read_lux(0b0000_0001, 0),
+        I2cTrans::write(DEV_BASE_ADDR, vec![Register::INT_ENABLE, 1]),
+    ];
+    let (client_stream, server_stream) = UnixStream::pair().unwrap();
+    let handle = thread::spawn(move || {
+        let mut server = Server::new(new(&transactions));
+        server.serve_connection(server_stream).unwrap();
+        destroy(server.destroy());
+    });
+    let mut client = Client::from_stream(client_stream).unwrap();
+    assert_eq!(Lux::new(0.72), client.read_lux().unwrap());
+    client.enable_interrupt().unwrap();
+    match client.set_integration_time(IntegrationTime::_100ms) {
+        Err(ClientError::Server(_)) => (),
+        _ => panic!("Error not returned."),
+    }
+    drop(client);
+    handle.join().unwrap();
+}
+
+#[test]
+fn server_serializes_requests_from_several_clients() {
+    const CLIENTS: usize = 2;
+    const REQUESTS: usize = 10;
+    let path = std::env::temp_dir().join(format!("max44009-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    // With write verification every request is a write followed by a read.
+    // Interleaved requests would not match the expected transactions.
+    let mut transactions = Vec::new();
+    for _ in 0..CLIENTS * REQUESTS {
+        transactions.push(I2cTrans::write(
+            DEV_BASE_ADDR,
+            vec![Register::UPPER_THRESHOLD_HIGH, 0b0111_1010],
+        ));
+        transactions.push(I2cTrans::write_read(
+            DEV_BASE_ADDR,
+            vec![Register::UPPER_THRESHOLD_HIGH],
+            vec![0b0111_1010],
+        ));
+    }
+    let mut dev = new(&transactions);
+    dev.enable_write_verification();
+    let stop = Arc::new(AtomicBool::new(false));
+    let server = {
+        let stop = stop.clone();
+        thread::spawn(move || Server::new(dev).run(listener, stop))
+    };
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|_| {
+            let mut client = Client::connect(&path).unwrap();
+            thread::spawn(move || {
+                for _ in 0..REQUESTS {
+                    client.set_upper_threshold(Lux::new(1000.0)).unwrap();
+                }
+                client
+            })
+        })
+        .collect();
+    // Keep the connections open until the server is stopped.
+    let clients: Vec<Client> = clients.into_iter().map(|c| c.join().unwrap()).collect();
+    stop.store(true, Ordering::SeqCst);
+    let server = server.join().unwrap().unwrap();
+    drop(clients);
+    destroy(server.destroy());
+    std::fs::remove_file(&path).unwrap();
+}
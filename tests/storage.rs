@@ -0,0 +1,106 @@
+use embedded_hal_mock::eh0::{i2c::Transaction as I2cTrans, MockError};
+use max44009::{
+    storage::{LoadError, MemoryStorage},
+    Calibration, Lux, MeasurementMode as MM,
+};
+use std::io::ErrorKind;
+mod common;
+use crate::common::{destroy, new, Register, DEV_BASE_ADDR};
+
+fn write(register: u8, value: u8) -> I2cTrans {
+    I2cTrans::write(DEV_BASE_ADDR, vec![register, value])
+}
+
+#[test]
+fn can_save_and_load() {
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::CONFIGURATION, 0b1000_0000),
+        write(Register::INT_ENABLE, 1),
+    ]);
+    let calibration = Calibration::new(2.0, Lux::new(0.0));
+    dev.set_calibration(calibration).unwrap();
+    dev.set_upper_threshold(Lux::new(2000.0)).unwrap();
+    dev.set_measurement_mode(MM::Continuous).unwrap();
+    dev.enable_interrupt().unwrap();
+    let mut storage = MemoryStorage::new();
+    dev.save(&mut storage).unwrap();
+    destroy(dev);
+
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0),
+        write(Register::THRESHOLD_TIMER, 0xFF),
+        write(Register::CONFIGURATION, 0b1000_0000),
+        write(Register::INT_ENABLE, 1),
+    ]);
+    dev.load(&mut storage).unwrap();
+    assert_eq!(&calibration, dev.calibration());
+    destroy(dev);
+}
+
+#[test]
+fn does_not_load_from_erased_storage() {
+    let mut dev = new(&[]);
+    match dev.load(&mut MemoryStorage::new()) {
+        Err(LoadError::NotFound) => (),
+        _ => panic!("Did not return LoadError::NotFound."),
+    }
+    destroy(dev);
+}
+
+#[test]
+fn does_not_load_corrupted_record() {
+    let mut dev = new(&[]);
+    let mut storage = MemoryStorage::new();
+    dev.save(&mut storage).unwrap();
+    storage.data_mut()[3] ^= 0x80;
+    match dev.load(&mut storage) {
+        Err(LoadError::ChecksumMismatch) => (),
+        _ => panic!("Did not return LoadError::ChecksumMismatch."),
+    }
+    destroy(dev);
+}
+
+#[test]
+fn keeps_written_registers_if_load_fails() {
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0010_1000),
+        write(Register::THRESHOLD_TIMER, 5),
+    ]);
+    dev.set_calibration(Calibration::new(2.0, Lux::new(0.0)))
+        .unwrap();
+    dev.set_upper_threshold(Lux::new(2000.0)).unwrap();
+    dev.set_lower_threshold(Lux::new(50.0)).unwrap();
+    dev.set_threshold_timer(5).unwrap();
+    let mut storage = MemoryStorage::new();
+    dev.save(&mut storage).unwrap();
+    destroy(dev);
+
+    let mut dev = new(&[
+        write(Register::UPPER_THRESHOLD_HIGH, 0b0111_1010),
+        write(Register::LOWER_THRESHOLD_HIGH, 0b0010_1000),
+        write(Register::THRESHOLD_TIMER, 5).with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::INT_ENABLE], vec![0]),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::CONFIGURATION], vec![0]),
+        I2cTrans::write_read(
+            DEV_BASE_ADDR,
+            vec![Register::UPPER_THRESHOLD_HIGH],
+            vec![0b0111_1010],
+        ),
+        I2cTrans::write_read(
+            DEV_BASE_ADDR,
+            vec![Register::LOWER_THRESHOLD_HIGH],
+            vec![0b0010_1000],
+        ),
+        I2cTrans::write_read(DEV_BASE_ADDR, vec![Register::THRESHOLD_TIMER], vec![0xFF]),
+    ]);
+    match dev.load(&mut storage) {
+        Err(LoadError::Device(_)) => (),
+        _ => panic!("Did not return LoadError::Device."),
+    }
+    assert_eq!(&Calibration::default(), dev.calibration());
+    assert!(!dev.restore_if_reset().unwrap());
+    destroy(dev);
+}